@@ -0,0 +1,100 @@
+/*
+Checks on raw bytes and on sequences of values extracted from the data, e.g. the timestamps or sequence numbers of records.
+*/
+use alloc::vec::Vec;
+
+use crate::{Error, Result};
+
+/// Returns the input as a str if it is valid UTF-8.
+pub fn utf8(data: &[u8]) -> Result<&str> {
+    core::str::from_utf8(data).map_err(|err| Error::InvalidUtf8 { offset: err.valid_up_to() })
+}
+
+/// Checks that the input is between `min` and `max` bytes long, inclusive.
+pub fn size(data: &[u8], min: usize, max: usize) -> Result<()> {
+    if data.len() < min {
+        return Err(Error::TooSmall { size: data.len(), min });
+    }
+    if data.len() > max {
+        return Err(Error::TooLarge { size: data.len(), max });
+    }
+    Ok(())
+}
+
+/// Checks that every timestamp is between `min` and `max`, inclusive. Use the same unit for all three.
+pub fn timestamp_range(timestamps: &[u64], min: u64, max: u64) -> Result<()> {
+    match timestamps.iter().position(|timestamp| *timestamp < min || *timestamp > max) {
+        Some(index) => Err(Error::OutOfRange { index }),
+        None => Ok(()),
+    }
+}
+
+/// Checks that every value is greater than the one before it, or greater or equal when `strict` is false.
+pub fn monotonic<T: PartialOrd>(values: &[T], strict: bool) -> Result<()> {
+    for (index, pair) in values.windows(2).enumerate() {
+        let ordered = if strict { pair[0] < pair[1] } else { pair[0] <= pair[1] };
+        if !ordered {
+            return Err(Error::NotMonotonic { index: index + 1 });
+        }
+    }
+    Ok(())
+}
+
+/// Checks that no value appears twice. Sorts an index of the values, so it costs O(n log n) and one allocation.
+/// Values already checked with `monotonic(values, true)` are unique and don't need this check.
+pub fn unique<T: Ord>(values: &[T]) -> Result<()> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_unstable_by(|a, b| values[*a].cmp(&values[*b]).then(a.cmp(b)));
+
+    for pair in order.windows(2) {
+        if values[pair[0]] == values[pair[1]] {
+            return Err(Error::Duplicate { index: pair[1] });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_reports_the_first_invalid_byte() {
+        assert_eq!(utf8("caf\u{e9}".as_bytes()), Ok("caf\u{e9}"));
+        assert_eq!(utf8(b"ab\xffcd"), Err(Error::InvalidUtf8 { offset: 2 }));
+    }
+
+    #[test]
+    fn size_is_inclusive() {
+        assert_eq!(size(b"abc", 3, 3), Ok(()));
+        assert_eq!(size(b"ab", 3, 5), Err(Error::TooSmall { size: 2, min: 3 }));
+        assert_eq!(size(b"abcdef", 3, 5), Err(Error::TooLarge { size: 6, max: 5 }));
+    }
+
+    #[test]
+    fn timestamp_range_is_inclusive() {
+        assert_eq!(timestamp_range(&[10, 15, 20], 10, 20), Ok(()));
+        assert_eq!(timestamp_range(&[10, 21, 9], 10, 20), Err(Error::OutOfRange { index: 1 }));
+    }
+
+    #[test]
+    fn monotonic_checks_the_order() {
+        assert_eq!(monotonic::<u64>(&[], true), Ok(()));
+        assert_eq!(monotonic(&[1], true), Ok(()));
+        assert_eq!(monotonic(&[1, 2, 5], true), Ok(()));
+        assert_eq!(monotonic(&[1, 2, 2, 3], false), Ok(()));
+        assert_eq!(monotonic(&[1, 2, 2, 3], true), Err(Error::NotMonotonic { index: 2 }));
+        assert_eq!(monotonic(&[3, 2], false), Err(Error::NotMonotonic { index: 1 }));
+        assert_eq!(monotonic(&["a", "b", "c"], true), Ok(()));
+        assert_eq!(monotonic(&[1.5, f64::NAN], false), Err(Error::NotMonotonic { index: 1 }));
+    }
+
+    #[test]
+    fn unique_reports_the_later_duplicate() {
+        assert_eq!(unique::<u64>(&[]), Ok(()));
+        assert_eq!(unique(&[3, 1, 2]), Ok(()));
+        assert_eq!(unique(&[3, 1, 3, 2]), Err(Error::Duplicate { index: 2 }));
+        assert_eq!(unique(&[5, 7, 1, 7, 5]), Err(Error::Duplicate { index: 4 }));
+        assert_eq!(unique(&["b", "a", "b"]), Err(Error::Duplicate { index: 2 }));
+    }
+}
@@ -0,0 +1,182 @@
+/*
+Single pass CSV validation (RFC 4180): quoted fields may contain delimiters, line breaks and doubled quotes,
+rows end with \n or \r\n, and every row must have the same number of columns.
+Blank lines at the end of the input are ignored, a blank line before another row is a row with one empty column.
+*/
+use crate::{Error, Result};
+
+pub struct Options<'a> {
+    pub delimiter: u8,
+    /// Expected names of the columns in the first row. Quoted names are compared without the surrounding quotes.
+    pub header: Option<&'a [&'a str]>,
+    /// Expected number of columns. Defaults to the length of the header, or to the number of columns in the first row.
+    pub columns: Option<usize>,
+}
+
+impl Default for Options<'_> {
+    fn default() -> Self {
+        Options { delimiter: b',', header: None, columns: None }
+    }
+}
+
+/// Checks the structure of the input and returns the number of data rows, not counting the header.
+pub fn validate(data: &[u8], options: &Options) -> Result<usize> {
+    visit_fields(data, options, &mut |_, _, _| Ok(()))
+}
+
+/// Like `validate`, also calling `visitor` with the row, column and content of every field after the header.
+/// Row numbers count from 0, including the header. Quoted fields are reported without the surrounding quotes.
+pub(crate) fn visit_fields<'a>(
+    data: &'a [u8],
+    options: &Options,
+    visitor: &mut dyn FnMut(usize, usize, &'a [u8]) -> Result<()>,
+) -> Result<usize> {
+    let mut expected = options.columns.or(options.header.map(|header| header.len()));
+    let mut row = 0;
+    let mut pos = 0;
+
+    while pos < data.len() {
+        if data[pos..].iter().all(|byte| matches!(byte, b'\n' | b'\r')) {
+            break;
+        }
+        let mut columns = 0;
+        loop {
+            let (field, next) = field(data, pos, options.delimiter, row)?;
+            match options.header {
+                Some(header) if row == 0 => {
+                    if header.get(columns).map(|name| name.as_bytes()) != Some(field) {
+                        return Err(Error::HeaderMismatch { column: columns });
+                    }
+                }
+                _ => visitor(row, columns, field)?,
+            }
+            columns += 1;
+            pos = next;
+
+            match data.get(pos) {
+                Some(byte) if *byte == options.delimiter => pos += 1,
+                Some(b'\n') => {
+                    pos += 1;
+                    break;
+                }
+                Some(b'\r') if data.get(pos + 1) == Some(&b'\n') => {
+                    pos += 2;
+                    break;
+                }
+                None => break,
+                Some(_) => return Err(Error::InvalidCsv { row, reason: "unexpected character after field" }),
+            }
+        }
+
+        let expected = *expected.get_or_insert(columns);
+        if columns != expected {
+            return Err(Error::ColumnCount { row, expected, found: columns });
+        }
+        row += 1;
+    }
+
+    if options.header.is_some() {
+        if row == 0 {
+            return Err(Error::InvalidCsv { row, reason: "missing header" });
+        }
+        row -= 1;
+    }
+    Ok(row)
+}
+
+/// Reads the field starting at `pos`. Returns its content, without surrounding quotes, and the position after it.
+fn field(data: &[u8], pos: usize, delimiter: u8, row: usize) -> Result<(&[u8], usize)> {
+    if data.get(pos) != Some(&b'"') {
+        let end = data[pos..]
+            .iter()
+            .position(|byte| *byte == delimiter || *byte == b'\n' || *byte == b'\r')
+            .map_or(data.len(), |len| pos + len);
+        if data[pos..end].contains(&b'"') {
+            return Err(Error::InvalidCsv { row, reason: "quote in unquoted field" });
+        }
+        return Ok((&data[pos..end], end));
+    }
+
+    let start = pos + 1;
+    let mut end = start;
+    loop {
+        match data.get(end) {
+            Some(b'"') if data.get(end + 1) == Some(&b'"') => end += 2,
+            Some(b'"') => return Ok((&data[start..end], end + 1)),
+            Some(_) => end += 1,
+            None => return Err(Error::InvalidCsv { row, reason: "unterminated quoted field" }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(data: &[u8]) -> Result<usize> {
+        validate(data, &Options::default())
+    }
+
+    #[test]
+    fn counts_rows() {
+        assert_eq!(rows(b""), Ok(0));
+        assert_eq!(rows(b"a,b\n1,2"), Ok(2));
+        assert_eq!(rows(b"a,b\n1,2\n"), Ok(2));
+        assert_eq!(validate(b"a;b\n1;2\n", &Options { delimiter: b';', ..Options::default() }), Ok(2));
+    }
+
+    #[test]
+    fn accepts_crlf_line_endings() {
+        assert_eq!(rows(b"a,b\r\n1,2\r\n"), Ok(2));
+        assert_eq!(rows(b"a,b\r\n1,2\n3,4"), Ok(3));
+    }
+
+    #[test]
+    fn ignores_trailing_blank_lines() {
+        assert_eq!(rows(b"a,b\n\n"), Ok(1));
+        assert_eq!(rows(b"a,b\r\n\r\n\n"), Ok(1));
+        assert_eq!(rows(b"\n"), Ok(0));
+        assert_eq!(rows(b"a,b\n\n1,2\n"), Err(Error::ColumnCount { row: 1, expected: 2, found: 1 }));
+    }
+
+    #[test]
+    fn quoted_fields_hold_delimiters_line_breaks_and_quotes() {
+        assert_eq!(rows(b"a,b\n\"1,2\",\"line\nbreak\"\n\"say \"\"hi\"\"\",\"\"\n"), Ok(3));
+
+        let mut fields = alloc::vec::Vec::new();
+        visit_fields(b"\"x,y\",\"a \"\"b\"\"\"\r\n", &Options::default(), &mut |row, column, field| {
+            fields.push((row, column, field));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(fields, [(0, 0, &b"x,y"[..]), (0, 1, &b"a \"\"b\"\""[..])]);
+    }
+
+    #[test]
+    fn rejects_invalid_quoting() {
+        assert_eq!(rows(b"a,b\n\"1,2\n"), Err(Error::InvalidCsv { row: 1, reason: "unterminated quoted field" }));
+        assert_eq!(rows(b"a,b\n1\"2,3\n"), Err(Error::InvalidCsv { row: 1, reason: "quote in unquoted field" }));
+        assert_eq!(
+            rows(b"\"a\"b,c\n"),
+            Err(Error::InvalidCsv { row: 0, reason: "unexpected character after field" })
+        );
+    }
+
+    #[test]
+    fn checks_the_column_count() {
+        assert_eq!(rows(b"a,b\n1,2,3\n"), Err(Error::ColumnCount { row: 1, expected: 2, found: 3 }));
+        assert_eq!(
+            validate(b"a,b\n1,2\n", &Options { columns: Some(3), ..Options::default() }),
+            Err(Error::ColumnCount { row: 0, expected: 3, found: 2 })
+        );
+    }
+
+    #[test]
+    fn checks_the_header() {
+        let header = Options { header: Some(&["time", "value"]), ..Options::default() };
+        assert_eq!(validate(b"time,value\n1,2\n3,4\n", &header), Ok(2));
+        assert_eq!(validate(b"\"time\",\"value\"\r\n1,2\r\n", &header), Ok(1));
+        assert_eq!(validate(b"time,amount\n1,2\n", &header), Err(Error::HeaderMismatch { column: 1 }));
+        assert_eq!(validate(b"", &header), Err(Error::InvalidCsv { row: 0, reason: "missing header" }));
+    }
+}
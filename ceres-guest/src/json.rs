@@ -0,0 +1,441 @@
+/*
+Single pass JSON validation (RFC 8259) that does not allocate. Nesting is limited to MAX_DEPTH to bound the stack
+used by the guest.
+*/
+use crate::{Error, Result};
+
+pub const MAX_DEPTH: usize = 64;
+
+/// Checks that the input is a single valid JSON value, surrounded by optional whitespace.
+pub fn validate(data: &[u8]) -> Result<()> {
+    Parser::new(data, &[]).document()
+}
+
+/// Checks that the input is a valid JSON object that has all of the `required` keys at the top level.
+/// Keys are compared with the raw text between the quotes, so they should not contain escapes. At most 64 keys can be required.
+pub fn validate_object(data: &[u8], required: &[&str]) -> Result<()> {
+    let mut parser = Parser::new(data, required);
+    parser.skip_whitespace();
+    if parser.peek() != Some(b'{') {
+        return parser.error("expected an object");
+    }
+    parser.document()?;
+
+    match (0..required.len().min(64)).find(|index| parser.found & (1 << index) == 0) {
+        Some(index) => Err(Error::MissingKey { index }),
+        None => Ok(()),
+    }
+}
+
+/// A value found while parsing. Strings and numbers are the raw bytes of the input, strings without the quotes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Value<'a> {
+    String(&'a [u8]),
+    Number(&'a [u8]),
+    Bool(bool),
+    Null,
+    Object,
+    Array,
+}
+
+pub(crate) enum Event<'a> {
+    Field { key: &'a [u8], value: Value<'a> },
+    RecordEnd,
+}
+
+/// Validates the input and reports the fields of its records: the input itself when it is an object, or every
+/// element when `array` is set and the input is an array of objects. Stops at the first error returned by `visitor`.
+pub(crate) fn visit_records<'a>(
+    data: &'a [u8],
+    array: bool,
+    visitor: &mut dyn FnMut(Event<'a>) -> Result<()>,
+) -> Result<()> {
+    let mut parser = Parser::new(data, &[]);
+    parser.record_depth = if array { 2 } else { 1 };
+    parser.visitor = Some(visitor);
+
+    parser.skip_whitespace();
+    let expected = if array { b'[' } else { b'{' };
+    if parser.peek() != Some(expected) {
+        return parser.error(if array { "expected an array" } else { "expected an object" });
+    }
+    parser.document()
+}
+
+struct Parser<'a, 'v> {
+    data: &'a [u8],
+    pos: usize,
+    depth: usize,
+    keys: &'a [&'a str],
+    /// Bit i is set when keys[i] was seen in the top level object.
+    found: u64,
+    /// Depth of the objects whose fields are reported to the visitor.
+    record_depth: usize,
+    visitor: Option<&'v mut dyn FnMut(Event<'a>) -> Result<()>>,
+}
+
+impl<'a, 'v> Parser<'a, 'v> {
+    fn new(data: &'a [u8], keys: &'a [&'a str]) -> Self {
+        Parser { data, pos: 0, depth: 0, keys, found: 0, record_depth: 0, visitor: None }
+    }
+
+    fn visit(&mut self, event: Event<'a>) -> Result<()> {
+        match self.visitor.as_mut() {
+            Some(visitor) => visitor(event),
+            None => Ok(()),
+        }
+    }
+
+    fn document(&mut self) -> Result<()> {
+        self.value()?;
+        self.skip_whitespace();
+        if self.pos != self.data.len() {
+            return self.error("unexpected data after the value");
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value<'a>> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.object().map(|_| Value::Object),
+            Some(b'[') => self.array().map(|_| Value::Array),
+            Some(b'"') => self.string().map(Value::String),
+            Some(b't') => self.literal(b"true").map(|_| Value::Bool(true)),
+            Some(b'f') => self.literal(b"false").map(|_| Value::Bool(false)),
+            Some(b'n') => self.literal(b"null").map(|_| Value::Null),
+            Some(b'-') | Some(b'0'..=b'9') => {
+                let start = self.pos;
+                self.number()?;
+                Ok(Value::Number(&self.data[start..self.pos]))
+            }
+            Some(_) => self.error("expected a value"),
+            None => self.error("unexpected end of input"),
+        }
+    }
+
+    fn object(&mut self) -> Result<()> {
+        self.enter()?;
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            return self.end_object();
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return self.error("expected a key");
+            }
+            let key = self.string()?;
+            if self.depth == 1 {
+                self.mark_key(key);
+            }
+
+            self.skip_whitespace();
+            if self.peek() != Some(b':') {
+                return self.error("expected ':'");
+            }
+            self.pos += 1;
+            let value = self.value()?;
+            if self.depth == self.record_depth {
+                self.visit(Event::Field { key, value })?;
+            }
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => return self.end_object(),
+                _ => return self.error("expected ',' or '}'"),
+            }
+        }
+    }
+
+    fn end_object(&mut self) -> Result<()> {
+        if self.depth == self.record_depth {
+            self.visit(Event::RecordEnd)?;
+        }
+        self.pos += 1;
+        self.depth -= 1;
+        Ok(())
+    }
+
+    fn array(&mut self) -> Result<()> {
+        self.enter()?;
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            self.depth -= 1;
+            return Ok(());
+        }
+
+        loop {
+            if self.depth + 1 == self.record_depth {
+                self.skip_whitespace();
+                if self.peek() != Some(b'{') {
+                    return self.error("expected an object");
+                }
+            }
+            self.value()?;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    self.depth -= 1;
+                    return Ok(());
+                }
+                _ => return self.error("expected ',' or ']'"),
+            }
+        }
+    }
+
+    /// Returns the raw bytes between the quotes.
+    fn string(&mut self) -> Result<&'a [u8]> {
+        self.pos += 1;
+        let start = self.pos;
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    let raw = &self.data[start..self.pos];
+                    self.pos += 1;
+                    return Ok(raw);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => self.pos += 1,
+                        Some(b'u') => {
+                            self.pos += 1;
+                            for _ in 0..4 {
+                                if !self.peek().is_some_and(|byte| byte.is_ascii_hexdigit()) {
+                                    return self.error("invalid unicode escape");
+                                }
+                                self.pos += 1;
+                            }
+                        }
+                        _ => return self.error("invalid escape"),
+                    }
+                }
+                Some(0x00..=0x1f) => return self.error("control character in string"),
+                Some(0x80..=0xff) => {
+                    // validate only the multi-byte sequence instead of the whole string
+                    let len = utf8_sequence_len(&self.data[self.pos..]);
+                    if len == 0 {
+                        return Err(Error::InvalidUtf8 { offset: self.pos });
+                    }
+                    self.pos += len;
+                }
+                Some(_) => self.pos += 1,
+                None => return self.error("unterminated string"),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<()> {
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => self.digits(),
+            _ => return self.error("invalid number"),
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            if !self.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+                return self.error("invalid number");
+            }
+            self.digits();
+        }
+        if let Some(b'e' | b'E') = self.peek() {
+            self.pos += 1;
+            if let Some(b'+' | b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if !self.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+                return self.error("invalid number");
+            }
+            self.digits();
+        }
+        Ok(())
+    }
+
+    fn digits(&mut self) {
+        while self.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+            self.pos += 1;
+        }
+    }
+
+    fn literal(&mut self, expected: &[u8]) -> Result<()> {
+        if self.data[self.pos..].starts_with(expected) {
+            self.pos += expected.len();
+            Ok(())
+        } else {
+            self.error("invalid literal")
+        }
+    }
+
+    fn enter(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return self.error("nesting is too deep");
+        }
+        Ok(())
+    }
+
+    fn mark_key(&mut self, key: &[u8]) {
+        for (index, required) in self.keys.iter().take(64).enumerate() {
+            if required.as_bytes() == key {
+                self.found |= 1 << index;
+            }
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn error<T>(&self, reason: &'static str) -> Result<T> {
+        Err(Error::InvalidJson { offset: self.pos, reason })
+    }
+}
+
+/// Length of the UTF-8 sequence at the start of `bytes`, or 0 if it is invalid.
+fn utf8_sequence_len(bytes: &[u8]) -> usize {
+    let len = match bytes[0] {
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        _ => return 0,
+    };
+    match bytes.get(..len) {
+        Some(sequence) if core::str::from_utf8(sequence).is_ok() => len,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{string::String, vec::Vec};
+
+    fn reason(data: &[u8]) -> &'static str {
+        match validate(data) {
+            Err(Error::InvalidJson { reason, .. }) => reason,
+            other => panic!("expected invalid JSON, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn accepts_valid_documents() {
+        for document in [
+            &b"{}"[..],
+            b"[]",
+            b" \t\r\n{ \"a\" : [1, -2.5, 3e10, 4E-2, 0.0] , \"b\": {\"c\": null} }\n",
+            b"\"text\"",
+            b"true",
+            b"false",
+            b"null",
+            b"-0",
+            b"[[], {}, \"\", [{}]]",
+        ] {
+            assert_eq!(validate(document), Ok(()), "{:?}", core::str::from_utf8(document));
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_documents() {
+        assert_eq!(reason(b""), "unexpected end of input");
+        assert_eq!(reason(b"{\"a\": 1,}"), "expected a key");
+        assert_eq!(reason(b"[1, 2,]"), "expected a value");
+        assert_eq!(reason(b"{\"a\" 1}"), "expected ':'");
+        assert_eq!(reason(b"{\"a\": 1"), "expected ',' or '}'");
+        assert_eq!(reason(b"[1 2]"), "expected ',' or ']'");
+        assert_eq!(reason(b"{} {}"), "unexpected data after the value");
+        assert_eq!(reason(b"01"), "unexpected data after the value");
+        assert_eq!(reason(b"1."), "invalid number");
+        assert_eq!(reason(b"1e"), "invalid number");
+        assert_eq!(reason(b"-"), "invalid number");
+        assert_eq!(reason(b"tru"), "invalid literal");
+        assert_eq!(reason(b"'a'"), "expected a value");
+        assert_eq!(reason(b"\"open"), "unterminated string");
+        assert_eq!(validate(b"[1, x]"), Err(Error::InvalidJson { offset: 4, reason: "expected a value" }));
+    }
+
+    #[test]
+    fn checks_string_escapes() {
+        assert_eq!(validate(br#""\" \\ \/ \b \f \n \r \t \u00e9 \uD83D\uDE00""#), Ok(()));
+        assert_eq!(reason(br#""\x""#), "invalid escape");
+        assert_eq!(reason(br#""\u12g4""#), "invalid unicode escape");
+        assert_eq!(reason(br#""\u12""#), "invalid unicode escape");
+        assert_eq!(reason(b"\"tab\there\""), "control character in string");
+    }
+
+    #[test]
+    fn checks_utf8_in_strings() {
+        assert_eq!(validate("\"caf\u{e9} \u{1f600}\"".as_bytes()), Ok(()));
+        assert_eq!(validate(b"\"\xc3\""), Err(Error::InvalidUtf8 { offset: 1 }));
+        assert_eq!(validate(b"\"a\xff\""), Err(Error::InvalidUtf8 { offset: 2 }));
+    }
+
+    #[test]
+    fn limits_the_nesting_depth() {
+        let nested = |depth: usize| {
+            let mut document = String::new();
+            (0..depth).for_each(|_| document.push('['));
+            (0..depth).for_each(|_| document.push(']'));
+            document
+        };
+        assert_eq!(validate(nested(MAX_DEPTH).as_bytes()), Ok(()));
+        assert_eq!(
+            validate(nested(MAX_DEPTH + 1).as_bytes()),
+            Err(Error::InvalidJson { offset: MAX_DEPTH, reason: "nesting is too deep" })
+        );
+    }
+
+    #[test]
+    fn validate_object_requires_top_level_keys() {
+        let data = br#"{"timestamp": 1, "nested": {"value": 2}}"#;
+        assert_eq!(validate_object(data, &["timestamp"]), Ok(()));
+        assert_eq!(validate_object(data, &["timestamp", "value"]), Err(Error::MissingKey { index: 1 }));
+        assert_eq!(
+            validate_object(b"[]", &[]),
+            Err(Error::InvalidJson { offset: 0, reason: "expected an object" })
+        );
+    }
+
+    #[test]
+    fn visits_the_fields_of_array_records() {
+        let mut events = Vec::new();
+        visit_records(br#"[{"a": "x", "b": [1]}, {"a": 2.5}]"#, true, &mut |event| {
+            events.push(match event {
+                Event::Field { key, value } => Some((key, value)),
+                Event::RecordEnd => None,
+            });
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            events,
+            [
+                Some((&b"a"[..], Value::String(b"x"))),
+                Some((&b"b"[..], Value::Array)),
+                None,
+                Some((&b"a"[..], Value::Number(b"2.5"))),
+                None,
+            ]
+        );
+        assert_eq!(
+            visit_records(b"[1]", true, &mut |_| Ok(())),
+            Err(Error::InvalidJson { offset: 1, reason: "expected an object" })
+        );
+    }
+}
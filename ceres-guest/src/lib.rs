@@ -0,0 +1,87 @@
+/*
+Checks that verifier authors need in `guestlib::verify`, written to be cheap in zkVM cycles:
+the validators make a single pass over the input bytes and never build a document tree.
+`ceres gen` adds this crate to the guest dependencies of every generated project.
+
+The crate is `no_std` (it only needs `alloc`), so it can also be used by guests built without the std feature.
+Collector signature verification is enabled with the `ed25519` and `secp256k1` features.
+
+    use ceres_guest::{checks, json};
+
+    pub fn verify(data: &Vec<u8>) -> bool {
+        checks::size(data, 1, 1024 * 1024).is_ok()
+            && json::validate_object(data, &["timestamp", "value"]).is_ok()
+    }
+*/
+#![no_std]
+
+extern crate alloc;
+
+pub mod checks;
+pub mod csv;
+pub mod json;
+pub mod schema;
+pub mod signature;
+
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The input is not valid UTF-8 from this byte on.
+    InvalidUtf8 { offset: usize },
+    TooSmall { size: usize, min: usize },
+    TooLarge { size: usize, max: usize },
+    /// The input is not valid JSON at this byte.
+    InvalidJson { offset: usize, reason: &'static str },
+    /// The top level JSON object has no key at this index of the required keys.
+    MissingKey { index: usize },
+    /// The input is not valid CSV on this row (counting from 0).
+    InvalidCsv { row: usize, reason: &'static str },
+    ColumnCount { row: usize, expected: usize, found: usize },
+    /// The CSV header does not have the expected name in this column.
+    HeaderMismatch { column: usize },
+    /// The value at this index is outside of the allowed range.
+    OutOfRange { index: usize },
+    /// The value at this index is not greater than (or equal to) the one before it.
+    NotMonotonic { index: usize },
+    /// The value at this index already appears earlier in the input.
+    Duplicate { index: usize },
+    /// The data is not a signed envelope produced by `ceres sign`.
+    InvalidEnvelope { reason: &'static str },
+    /// The envelope uses a signature scheme that is unknown or not enabled with a crate feature.
+    UnsupportedScheme,
+    /// The envelope was signed with a different key than the trusted collector key.
+    UntrustedKey,
+    InvalidSignature,
+    /// A field of this record (counting from 0) does not match the field at this index of the schema.
+    SchemaViolation { record: usize, field: usize, reason: &'static str },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidUtf8 { offset } => write!(f, "invalid UTF-8 at byte {}", offset),
+            Error::TooSmall { size, min } => write!(f, "{} bytes is less than the minimum of {}", size, min),
+            Error::TooLarge { size, max } => write!(f, "{} bytes is more than the maximum of {}", size, max),
+            Error::InvalidJson { offset, reason } => write!(f, "invalid JSON at byte {}: {}", offset, reason),
+            Error::MissingKey { index } => write!(f, "required key {} is missing", index),
+            Error::InvalidCsv { row, reason } => write!(f, "invalid CSV on row {}: {}", row, reason),
+            Error::ColumnCount { row, expected, found } => {
+                write!(f, "row {} has {} columns, expected {}", row, found, expected)
+            }
+            Error::HeaderMismatch { column } => write!(f, "unexpected header in column {}", column),
+            Error::OutOfRange { index } => write!(f, "value {} is out of range", index),
+            Error::NotMonotonic { index } => write!(f, "value {} breaks the sequence order", index),
+            Error::Duplicate { index } => write!(f, "value {} is a duplicate", index),
+            Error::InvalidEnvelope { reason } => write!(f, "invalid signed envelope: {}", reason),
+            Error::UnsupportedScheme => write!(f, "unsupported signature scheme"),
+            Error::UntrustedKey => write!(f, "the data was not signed by the trusted collector key"),
+            Error::InvalidSignature => write!(f, "invalid signature"),
+            Error::SchemaViolation { record, field, reason } => {
+                write!(f, "record {}, field {}: {}", record, field, reason)
+            }
+        }
+    }
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
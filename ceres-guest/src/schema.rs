@@ -0,0 +1,319 @@
+/*
+Declarative validation of structured data. `ceres gen` turns verifier/schema.json into a `Schema` constant and a
+`guestlib::verify` that calls `validate`, so verifiers that only check the structure of the data need no hand-written code.
+
+Records are the top level object (Format::Json), every object of a top level array (Format::JsonArray), or every row
+after the header (Format::Csv, where the header must list the field names in order). Fields that are not in the schema
+are allowed in JSON records.
+*/
+use alloc::vec::Vec;
+
+use crate::{
+    checks, csv,
+    json::{self, Event, Value},
+    Error, Result,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    JsonArray,
+    Csv,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Field<'a> {
+    pub name: &'a str,
+    pub field_type: FieldType,
+    /// Required fields can't be missing, null, or empty in CSV.
+    pub required: bool,
+    /// Bounds of integer and number values, inclusive.
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    /// Bounds of the length of string values in bytes, as written in the data, inclusive.
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    /// No two records have the same value, compared as written in the data.
+    pub unique: bool,
+    /// Every value is greater than or equal to the value of the record before it. Integer and number fields only.
+    pub monotonic: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Schema<'a> {
+    pub format: Format,
+    /// Bounds of the size of the data in bytes, inclusive.
+    pub min_size: usize,
+    pub max_size: usize,
+    pub fields: &'a [Field<'a>],
+}
+
+/// Checks the data against the schema and returns the number of records.
+pub fn validate(data: &[u8], schema: &Schema) -> Result<usize> {
+    checks::size(data, schema.min_size, schema.max_size)?;
+
+    let mut state = State::new(schema);
+    let records = match schema.format {
+        Format::Json | Format::JsonArray => {
+            let mut seen: Vec<bool> = alloc::vec![false; schema.fields.len()];
+            json::visit_records(data, schema.format == Format::JsonArray, &mut |event| match event {
+                Event::Field { key, value } => {
+                    match schema.fields.iter().position(|field| field.name.as_bytes() == key) {
+                        Some(index) => {
+                            seen[index] = true;
+                            state.json_value(index, value)
+                        }
+                        None => Ok(()),
+                    }
+                }
+                Event::RecordEnd => {
+                    let missing = schema.fields.iter().zip(&seen).position(|(field, seen)| field.required && !seen);
+                    if let Some(field) = missing {
+                        return Err(Error::SchemaViolation { record: state.record, field, reason: "missing field" });
+                    }
+                    seen.iter_mut().for_each(|seen| *seen = false);
+                    state.record += 1;
+                    Ok(())
+                }
+            })?;
+            state.record
+        }
+        Format::Csv => {
+            let header: Vec<&str> = schema.fields.iter().map(|field| field.name).collect();
+            let options = csv::Options { header: Some(&header), ..Default::default() };
+            csv::visit_fields(data, &options, &mut |row, column, text| {
+                state.record = row - 1;
+                if column < schema.fields.len() {
+                    state.text_value(column, text)
+                } else {
+                    Ok(())
+                }
+            })?
+        }
+    };
+
+    state.check_sequences()?;
+    Ok(records)
+}
+
+struct State<'a, 's> {
+    schema: &'s Schema<'s>,
+    record: usize,
+    /// (record, value as written) of every unique field, by field index.
+    unique: Vec<Vec<(usize, &'a [u8])>>,
+    /// (record, value) of every monotonic field, by field index.
+    monotonic: Vec<Vec<(usize, f64)>>,
+}
+
+impl<'a, 's> State<'a, 's> {
+    fn new(schema: &'s Schema<'s>) -> Self {
+        State {
+            schema,
+            record: 0,
+            unique: schema.fields.iter().map(|_| Vec::new()).collect(),
+            monotonic: schema.fields.iter().map(|_| Vec::new()).collect(),
+        }
+    }
+
+    fn json_value(&mut self, index: usize, value: Value<'a>) -> Result<()> {
+        let field = &self.schema.fields[index];
+        match (field.field_type, value) {
+            (_, Value::Null) if field.required => self.violation(index, "null value"),
+            (_, Value::Null) => Ok(()),
+            (FieldType::String, Value::String(raw)) => self.string(index, raw),
+            (FieldType::Integer | FieldType::Number, Value::Number(raw)) => self.number(index, raw),
+            (FieldType::Boolean, Value::Bool(_)) => Ok(()),
+            _ => self.violation(index, "wrong type"),
+        }
+    }
+
+    fn text_value(&mut self, index: usize, text: &'a [u8]) -> Result<()> {
+        let field = &self.schema.fields[index];
+        match field.field_type {
+            _ if text.is_empty() && field.required => self.violation(index, "empty value"),
+            _ if text.is_empty() => Ok(()),
+            FieldType::String => self.string(index, text),
+            FieldType::Integer | FieldType::Number => self.number(index, text),
+            FieldType::Boolean if text == b"true" || text == b"false" => Ok(()),
+            FieldType::Boolean => self.violation(index, "wrong type"),
+        }
+    }
+
+    fn string(&mut self, index: usize, raw: &'a [u8]) -> Result<()> {
+        let field = &self.schema.fields[index];
+        if field.min_length.is_some_and(|min| raw.len() < min) || field.max_length.is_some_and(|max| raw.len() > max) {
+            return self.violation(index, "length out of range");
+        }
+        if field.unique {
+            self.unique[index].push((self.record, raw));
+        }
+        Ok(())
+    }
+
+    fn number(&mut self, index: usize, raw: &'a [u8]) -> Result<()> {
+        let field = &self.schema.fields[index];
+        let text = core::str::from_utf8(raw).unwrap_or("");
+        let value = match field.field_type {
+            FieldType::Integer => text.parse::<i64>().ok().map(|value| value as f64),
+            _ => text.parse::<f64>().ok().filter(|value| value.is_finite()),
+        };
+        let value = match value {
+            Some(value) => value,
+            None => return self.violation(index, "wrong type"),
+        };
+
+        if field.min.is_some_and(|min| value < min) || field.max.is_some_and(|max| value > max) {
+            return self.violation(index, "value out of range");
+        }
+        if field.unique {
+            self.unique[index].push((self.record, raw));
+        }
+        if field.monotonic {
+            self.monotonic[index].push((self.record, value));
+        }
+        Ok(())
+    }
+
+    fn check_sequences(&self) -> Result<()> {
+        for (field, values) in self.monotonic.iter().enumerate() {
+            let numbers: Vec<f64> = values.iter().map(|(_, value)| *value).collect();
+            if let Err(Error::NotMonotonic { index }) = checks::monotonic(&numbers, false) {
+                return Err(Error::SchemaViolation { record: values[index].0, field, reason: "value out of order" });
+            }
+        }
+        for (field, values) in self.unique.iter().enumerate() {
+            let raw: Vec<&[u8]> = values.iter().map(|(_, value)| *value).collect();
+            if let Err(Error::Duplicate { index }) = checks::unique(&raw) {
+                return Err(Error::SchemaViolation { record: values[index].0, field, reason: "duplicate value" });
+            }
+        }
+        Ok(())
+    }
+
+    fn violation(&self, field: usize, reason: &'static str) -> Result<()> {
+        Err(Error::SchemaViolation { record: self.record, field, reason })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIELD: Field = Field {
+        name: "",
+        field_type: FieldType::String,
+        required: false,
+        min: None,
+        max: None,
+        min_length: None,
+        max_length: None,
+        unique: false,
+        monotonic: false,
+    };
+
+    const FIELDS: &[Field] = &[
+        Field { name: "time", field_type: FieldType::Integer, required: true, monotonic: true, ..FIELD },
+        Field { name: "id", field_type: FieldType::String, unique: true, max_length: Some(4), ..FIELD },
+        Field { name: "value", field_type: FieldType::Number, min: Some(-1.0), max: Some(1.0), ..FIELD },
+        Field { name: "ok", field_type: FieldType::Boolean, ..FIELD },
+    ];
+
+    fn schema(format: Format) -> Schema<'static> {
+        Schema { format, min_size: 0, max_size: 1024, fields: FIELDS }
+    }
+
+    fn violation(record: usize, field: usize, reason: &'static str) -> Result<usize> {
+        Err(Error::SchemaViolation { record, field, reason })
+    }
+
+    #[test]
+    fn validates_json_array_records() {
+        let schema = schema(Format::JsonArray);
+        let data = br#"[
+            {"time": 1, "id": "a", "value": 0.5, "ok": true, "extra": [1, 2]},
+            {"time": 1, "id": "b", "value": null},
+            {"time": 3}
+        ]"#;
+        assert_eq!(validate(data, &schema), Ok(3));
+        assert_eq!(validate(b"[]", &schema), Ok(0));
+        assert_eq!(
+            validate(b"{}", &schema),
+            Err(Error::InvalidJson { offset: 0, reason: "expected an array" })
+        );
+    }
+
+    #[test]
+    fn validates_a_json_object() {
+        let schema = schema(Format::Json);
+        assert_eq!(validate(br#"{"time": 5, "id": "a"}"#, &schema), Ok(1));
+        assert_eq!(validate(br#"{"id": "a"}"#, &schema), violation(0, 0, "missing field"));
+    }
+
+    #[test]
+    fn checks_required_fields() {
+        let schema = schema(Format::JsonArray);
+        assert_eq!(validate(br#"[{"time": 1}, {"id": "b"}]"#, &schema), violation(1, 0, "missing field"));
+        assert_eq!(validate(br#"[{"time": null}]"#, &schema), violation(0, 0, "null value"));
+
+        let schema = self::schema(Format::Csv);
+        assert_eq!(validate(b"time,id,value,ok\n1,a,,\n,b,,\n", &schema), violation(1, 0, "empty value"));
+    }
+
+    #[test]
+    fn checks_types_and_bounds() {
+        let schema = schema(Format::JsonArray);
+        assert_eq!(validate(br#"[{"time": "1"}]"#, &schema), violation(0, 0, "wrong type"));
+        assert_eq!(validate(br#"[{"time": 1.5}]"#, &schema), violation(0, 0, "wrong type"));
+        assert_eq!(validate(br#"[{"time": 1, "ok": 1}]"#, &schema), violation(0, 3, "wrong type"));
+        assert_eq!(validate(br#"[{"time": 1, "value": 1.5}]"#, &schema), violation(0, 2, "value out of range"));
+        assert_eq!(validate(br#"[{"time": 1, "id": "abcde"}]"#, &schema), violation(0, 1, "length out of range"));
+
+        let schema = self::schema(Format::Csv);
+        assert_eq!(validate(b"time,id,value,ok\n1,a,0,yes\n", &schema), violation(0, 3, "wrong type"));
+        assert_eq!(validate(b"time,id,value,ok\nnow,a,0,true\n", &schema), violation(0, 0, "wrong type"));
+    }
+
+    #[test]
+    fn checks_unique_fields() {
+        let schema = schema(Format::JsonArray);
+        assert_eq!(
+            validate(br#"[{"time": 1, "id": "a"}, {"time": 2, "id": "b"}, {"time": 3, "id": "a"}]"#, &schema),
+            violation(2, 1, "duplicate value")
+        );
+
+        let schema = self::schema(Format::Csv);
+        assert_eq!(validate(b"time,id,value,ok\n1,a,,\n2,b,,\n", &schema), Ok(2));
+        assert_eq!(validate(b"time,id,value,ok\n1,a,,\n2,\"a\",,\n", &schema), violation(1, 1, "duplicate value"));
+    }
+
+    #[test]
+    fn checks_monotonic_fields() {
+        let schema = schema(Format::JsonArray);
+        assert_eq!(
+            validate(br#"[{"time": 1}, {"time": 5}, {"time": 4}]"#, &schema),
+            violation(2, 0, "value out of order")
+        );
+
+        let schema = self::schema(Format::Csv);
+        assert_eq!(validate(b"time,id,value,ok\r\n1,,,\r\n1,,,\r\n2,,,\r\n", &schema), Ok(3));
+        assert_eq!(validate(b"time,id,value,ok\n2,,,\n1,,,\n", &schema), violation(1, 0, "value out of order"));
+    }
+
+    #[test]
+    fn checks_the_csv_header_and_size() {
+        let schema = schema(Format::Csv);
+        assert_eq!(validate(b"time,id,value,ok\n", &schema), Ok(0));
+        assert_eq!(validate(b"time,id,amount,ok\n1,a,,\n", &schema), Err(Error::HeaderMismatch { column: 2 }));
+
+        let schema = Schema { min_size: 20, ..schema };
+        assert_eq!(validate(b"time,id,value,ok\n", &schema), Err(Error::TooSmall { size: 17, min: 20 }));
+    }
+}
@@ -0,0 +1,232 @@
+/*
+Collector signatures. A collector signs the data it collected (`ceres sign`) and sends a signed envelope:
+
+    "CSIG" | version (1 byte) | scheme (1 byte) | public key | signature (64 bytes) | payload
+
+The public key is 32 bytes for ed25519 and 33 bytes (SEC1 compressed) for secp256k1. secp256k1 signatures are
+ECDSA over the SHA-256 of the payload, in the 64 byte r || s form.
+Verifying a scheme needs the crate feature of the same name.
+*/
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+
+use crate::{Error, Result};
+
+pub const MAGIC: &[u8; 4] = b"CSIG";
+pub const VERSION: u8 = 1;
+pub const SIGNATURE_LEN: usize = 64;
+const HEADER_LEN: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    Ed25519 = 1,
+    Secp256k1 = 2,
+}
+
+impl Scheme {
+    pub fn from_byte(byte: u8) -> Option<Scheme> {
+        match byte {
+            1 => Some(Scheme::Ed25519),
+            2 => Some(Scheme::Secp256k1),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Scheme> {
+        match name {
+            "ed25519" => Some(Scheme::Ed25519),
+            "secp256k1" => Some(Scheme::Secp256k1),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Scheme::Ed25519 => "ed25519",
+            Scheme::Secp256k1 => "secp256k1",
+        }
+    }
+
+    pub fn public_key_len(self) -> usize {
+        match self {
+            Scheme::Ed25519 => 32,
+            Scheme::Secp256k1 => 33,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct SignedData<'a> {
+    pub scheme: Scheme,
+    pub public_key: &'a [u8],
+    pub signature: &'a [u8],
+    pub payload: &'a [u8],
+}
+
+impl SignedData<'_> {
+    /// The signer public key in lowercase hex, as committed to the journal by generated guests.
+    pub fn public_key_hex(&self) -> String {
+        let mut hex = String::with_capacity(self.public_key.len() * 2);
+        for byte in self.public_key {
+            let _ = write!(hex, "{:02x}", byte);
+        }
+        hex
+    }
+}
+
+/// Splits an envelope into its parts without checking the signature.
+pub fn open(envelope: &[u8]) -> Result<SignedData<'_>> {
+    if envelope.len() < HEADER_LEN || &envelope[..4] != MAGIC {
+        return Err(Error::InvalidEnvelope { reason: "not a signed envelope" });
+    }
+    if envelope[4] != VERSION {
+        return Err(Error::InvalidEnvelope { reason: "unsupported envelope version" });
+    }
+    let scheme = Scheme::from_byte(envelope[5]).ok_or(Error::UnsupportedScheme)?;
+
+    let key_end = HEADER_LEN + scheme.public_key_len();
+    let signature_end = key_end + SIGNATURE_LEN;
+    if envelope.len() < signature_end {
+        return Err(Error::InvalidEnvelope { reason: "envelope is truncated" });
+    }
+
+    Ok(SignedData {
+        scheme,
+        public_key: &envelope[HEADER_LEN..key_end],
+        signature: &envelope[key_end..signature_end],
+        payload: &envelope[signature_end..],
+    })
+}
+
+/// Builds an envelope from a payload and its signature.
+pub fn encode(scheme: Scheme, public_key: &[u8], signature: &[u8], payload: &[u8]) -> Vec<u8> {
+    let mut envelope = Vec::with_capacity(HEADER_LEN + public_key.len() + signature.len() + payload.len());
+    envelope.extend_from_slice(MAGIC);
+    envelope.push(VERSION);
+    envelope.push(scheme as u8);
+    envelope.extend_from_slice(public_key);
+    envelope.extend_from_slice(signature);
+    envelope.extend_from_slice(payload);
+    envelope
+}
+
+/// Checks that the envelope was signed by `trusted_key` and returns its parts.
+pub fn verify<'a>(envelope: &'a [u8], scheme: Scheme, trusted_key: &[u8]) -> Result<SignedData<'a>> {
+    let signed = open(envelope)?;
+    if signed.scheme != scheme || signed.public_key != trusted_key {
+        return Err(Error::UntrustedKey);
+    }
+    verify_signature(&signed)?;
+    Ok(signed)
+}
+
+fn verify_signature(signed: &SignedData) -> Result<()> {
+    match signed.scheme {
+        #[cfg(feature = "ed25519")]
+        Scheme::Ed25519 => {
+            use ed25519_dalek::{Signature, VerifyingKey};
+
+            let key: &[u8; 32] = signed.public_key.try_into().map_err(|_| Error::InvalidSignature)?;
+            let key = VerifyingKey::from_bytes(key).map_err(|_| Error::InvalidSignature)?;
+            let signature = Signature::from_slice(signed.signature).map_err(|_| Error::InvalidSignature)?;
+            key.verify_strict(signed.payload, &signature).map_err(|_| Error::InvalidSignature)
+        }
+        #[cfg(feature = "secp256k1")]
+        Scheme::Secp256k1 => {
+            use k256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+
+            let key = VerifyingKey::from_sec1_bytes(signed.public_key).map_err(|_| Error::InvalidSignature)?;
+            let signature = Signature::from_slice(signed.signature).map_err(|_| Error::InvalidSignature)?;
+            key.verify(signed.payload, &signature).map_err(|_| Error::InvalidSignature)
+        }
+        #[allow(unreachable_patterns)]
+        _ => Err(Error::UnsupportedScheme),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD: &[u8] = b"{\"temperature\": 21}";
+
+    #[cfg(feature = "ed25519")]
+    fn ed25519_envelope(secret: [u8; 32], payload: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        use ed25519_dalek::{Signer, SigningKey};
+
+        let key = SigningKey::from_bytes(&secret);
+        let public_key = key.verifying_key().to_bytes().to_vec();
+        let envelope = encode(Scheme::Ed25519, &public_key, &key.sign(payload).to_bytes(), payload);
+        (envelope, public_key)
+    }
+
+    #[test]
+    fn rejects_malformed_envelopes() {
+        let envelope = encode(Scheme::Ed25519, &[7; 32], &[9; SIGNATURE_LEN], PAYLOAD);
+        assert!(open(&envelope).is_ok());
+
+        let mut bad_magic = envelope.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(open(&bad_magic).err(), Some(Error::InvalidEnvelope { reason: "not a signed envelope" }));
+        assert_eq!(open(b"CSI").err(), Some(Error::InvalidEnvelope { reason: "not a signed envelope" }));
+
+        let mut bad_version = envelope.clone();
+        bad_version[4] = VERSION + 1;
+        assert_eq!(open(&bad_version).err(), Some(Error::InvalidEnvelope { reason: "unsupported envelope version" }));
+
+        let mut bad_scheme = envelope.clone();
+        bad_scheme[5] = 3;
+        assert_eq!(open(&bad_scheme).err(), Some(Error::UnsupportedScheme));
+
+        let truncated = &envelope[..HEADER_LEN + 32 + SIGNATURE_LEN - 1];
+        assert_eq!(open(truncated).err(), Some(Error::InvalidEnvelope { reason: "envelope is truncated" }));
+    }
+
+    #[test]
+    fn splits_an_envelope_into_its_parts() {
+        let envelope = encode(Scheme::Secp256k1, &[2; 33], &[9; SIGNATURE_LEN], PAYLOAD);
+        let signed = open(&envelope).unwrap();
+        assert_eq!(signed.scheme, Scheme::Secp256k1);
+        assert_eq!(signed.public_key, &[2; 33]);
+        assert_eq!(signed.signature, &[9; SIGNATURE_LEN]);
+        assert_eq!(signed.payload, PAYLOAD);
+        assert_eq!(signed.public_key_hex(), "02".repeat(33));
+    }
+
+    #[test]
+    fn rejects_an_untrusted_key() {
+        let envelope = encode(Scheme::Ed25519, &[7; 32], &[9; SIGNATURE_LEN], PAYLOAD);
+        assert_eq!(verify(&envelope, Scheme::Ed25519, &[8; 32]).err(), Some(Error::UntrustedKey));
+        assert_eq!(verify(&envelope, Scheme::Secp256k1, &[7; 32]).err(), Some(Error::UntrustedKey));
+    }
+
+    #[cfg(feature = "ed25519")]
+    #[test]
+    fn verifies_ed25519_signatures() {
+        let (envelope, public_key) = ed25519_envelope([1; 32], PAYLOAD);
+        assert_eq!(verify(&envelope, Scheme::Ed25519, &public_key).unwrap().payload, PAYLOAD);
+
+        let mut tampered = envelope.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(verify(&tampered, Scheme::Ed25519, &public_key).err(), Some(Error::InvalidSignature));
+
+        let (_, other_key) = ed25519_envelope([2; 32], PAYLOAD);
+        assert_eq!(verify(&envelope, Scheme::Ed25519, &other_key).err(), Some(Error::UntrustedKey));
+    }
+
+    #[cfg(feature = "secp256k1")]
+    #[test]
+    fn verifies_secp256k1_signatures() {
+        use k256::ecdsa::{signature::Signer, Signature, SigningKey};
+
+        let key = SigningKey::from_slice(&[1; 32]).unwrap();
+        let public_key = key.verifying_key().to_encoded_point(true).as_bytes().to_vec();
+        let signature: Signature = key.sign(PAYLOAD);
+        let envelope = encode(Scheme::Secp256k1, &public_key, &signature.to_bytes(), PAYLOAD);
+        assert_eq!(verify(&envelope, Scheme::Secp256k1, &public_key).unwrap().payload, PAYLOAD);
+
+        let mut tampered = envelope.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(verify(&tampered, Scheme::Secp256k1, &public_key).err(), Some(Error::InvalidSignature));
+    }
+}
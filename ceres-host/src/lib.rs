@@ -0,0 +1,136 @@
+/*
+The context `hostlib::prepare` receives in verifiers generated by ceres: the input files, the options given on the
+command line, the values in verifier/config.json and the folder where the receipt is written.
+
+The verifier binary is run as `<name> [--option=value | --flag]... [INPUT]...`:
+- `--output-dir=DIR` sets the output folder, the working directory by default.
+- any other `--name=value` is an option, and `--name` alone is the option `name` set to "true".
+- everything else is an input path.
+
+    use ceres_host::Context;
+
+    pub fn prepare(ctx: &Context) -> Vec<u8> {
+        std::fs::read(ctx.input().expect("no input file")).unwrap()
+    }
+
+Projects whose `prepare` still takes `args: Vec<String>` keep working: the generated host passes `ctx.args.clone()`.
+*/
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct Context {
+    /// The process arguments, including the binary name, as passed to the old `prepare(args: Vec<String>)`.
+    pub args: Vec<String>,
+    pub inputs: Vec<PathBuf>,
+    pub options: BTreeMap<String, String>,
+    /// Values from verifier/config.json, embedded in the host by `ceres gen`.
+    pub config: BTreeMap<String, String>,
+    pub output_dir: PathBuf,
+}
+
+impl Context {
+    /// Parses the process arguments, the first of which is the binary name.
+    pub fn from_args(args: Vec<String>) -> Context {
+        let mut context = Context {
+            output_dir: PathBuf::from("."),
+            ..Default::default()
+        };
+
+        for arg in args.iter().skip(1) {
+            match arg.strip_prefix("--") {
+                Some(option) => {
+                    let (name, value) = option.split_once('=').unwrap_or((option, "true"));
+                    if name == "output-dir" {
+                        context.output_dir = PathBuf::from(value);
+                    } else {
+                        context.options.insert(name.to_string(), value.to_string());
+                    }
+                }
+                None => context.inputs.push(PathBuf::from(arg)),
+            }
+        }
+
+        context.args = args;
+        context
+    }
+
+    /// Adds project config values, generated by `ceres gen` from verifier/config.json.
+    pub fn with_config(mut self, config: &[(&str, &str)]) -> Context {
+        for (name, value) in config {
+            self.config.insert(name.to_string(), value.to_string());
+        }
+        self
+    }
+
+    /// The first input path.
+    pub fn input(&self) -> Option<&Path> {
+        self.inputs.first().map(|input| input.as_path())
+    }
+
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(|value| value.as_str())
+    }
+
+    /// True when the option was given as `--name` or `--name=true`.
+    pub fn flag(&self, name: &str) -> bool {
+        self.option(name) == Some("true")
+    }
+
+    pub fn config_value(&self, name: &str) -> Option<&str> {
+        self.config.get(name).map(|value| value.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(args: &[&str]) -> Context {
+        Context::from_args(args.iter().map(|arg| arg.to_string()).collect())
+    }
+
+    #[test]
+    fn parses_options_flags_and_inputs() {
+        let ctx = context(&["weather", "--output-dir=out", "--params=params.json", "--verbose", "a.csv", "b.csv"]);
+        assert_eq!(ctx.output_dir, PathBuf::from("out"));
+        assert_eq!(ctx.option("params"), Some("params.json"));
+        assert!(ctx.flag("verbose"));
+        assert!(!ctx.flag("params"));
+        assert!(!ctx.flag("quiet"));
+        assert_eq!(ctx.option("output-dir"), None);
+        assert_eq!(ctx.inputs, [PathBuf::from("a.csv"), PathBuf::from("b.csv")]);
+        assert_eq!(ctx.input(), Some(Path::new("a.csv")));
+        assert_eq!(ctx.args.len(), 6);
+    }
+
+    #[test]
+    fn splits_options_at_the_first_equals_sign() {
+        let ctx = context(&["weather", "--filter=a=b", "--empty=", "--debug=true"]);
+        assert_eq!(ctx.option("filter"), Some("a=b"));
+        assert_eq!(ctx.option("empty"), Some(""));
+        assert!(ctx.flag("debug"));
+    }
+
+    #[test]
+    fn defaults_without_arguments() {
+        let ctx = context(&["weather"]);
+        assert_eq!(ctx.output_dir, PathBuf::from("."));
+        assert!(ctx.inputs.is_empty());
+        assert_eq!(ctx.input(), None);
+        assert!(ctx.options.is_empty());
+        assert_eq!(Context::from_args(Vec::new()).input(), None);
+    }
+
+    #[test]
+    fn with_config_adds_the_config_values() {
+        let ctx = context(&["weather", "--station=1"]).with_config(&[("station", "2"), ("unit", "celsius")]);
+        assert_eq!(ctx.config_value("station"), Some("2"));
+        assert_eq!(ctx.config_value("unit"), Some("celsius"));
+        assert_eq!(ctx.config_value("missing"), None);
+        // config values and options are kept apart
+        assert_eq!(ctx.option("station"), Some("1"));
+    }
+}
@@ -1,48 +1,354 @@
 /*
-- Create the executable binary from the code
-- generate the image ID for the guest
+- Re-sync the generated project if the verifier source changed since it was generated
+- Create the executable binary from the code, for this machine or, with a target triple, a statically linked one for another platform
+- compute the image ID of the guest from its ELF
+- record the image ID, ELF hash and toolchain versions in ceres.lock
 */
-use std::{env, fs, path::Path, process::Command};
-use execute::Execute;
+use std::{env, fs, path::{Path, PathBuf}, process::Command};
 
-pub fn build() {
-    let pwd: String = get_pwd_name();
-    let substrings: Vec<&str> = pwd.split('/').collect();
-    let project_name = substrings.last().unwrap();
+use crate::{
+    error::{CeresError, IoContext, Result},
+    gen, image_id, lock,
+    project::Project,
+};
 
-    if let Err(err) = env::set_current_dir(Path::new("verifier").join("out").join(project_name)) {
-        eprintln!("Failed to change working directory: {}", err);
-    }
+#[derive(Debug, Clone, Default)]
+pub struct BuildOptions {
+    /// Fail when the generated project is stale, instead of re-syncing it with the verifier folder first.
+    pub no_regen: bool,
+    /// Target triple of the host binary, e.g. x86_64-unknown-linux-musl. The binary is linked statically.
+    pub target: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct BuildResult {
+    /// What was written to ceres.lock, including the image ID.
+    pub lock: lock::Lock,
+    pub elf_path: PathBuf,
+    pub host_binary: PathBuf,
+    /// Whether the host binary is statically linked, when it could be checked.
+    pub static_binary: Option<bool>,
+}
+
+#[derive(Debug)]
+pub struct ReproducibilityReport {
+    pub builds: Vec<ReproducibleBuild>,
+    pub reproducible: bool,
+    /// Likely causes of divergence, empty if the builds match.
+    pub causes: Vec<String>,
+    /// The image ID recorded in ceres.lock by the last `ceres build`, if any.
+    pub lock_image_id: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ReproducibleBuild {
+    pub elf_sha256: String,
+    pub image_id: String,
+}
+
+pub fn build(project: &Project, options: &BuildOptions) -> Result<BuildResult> {
+    let project_name = project.name();
+    let out_dir = project.out_dir();
 
     let mut command = Command::new("cargo");
-    command.arg("run").arg("--release").arg("get-img-id");
+    command.current_dir(&out_dir).arg("build").arg("--release");
+    if let Some(target) = &options.target {
+        configure_target(&mut command, target)?;
+    }
 
-    let output = command.execute_output().unwrap();
+    ensure_generated(project, options)?;
+    // hashed before building, so an edit made during the build leaves ceres.lock stale
+    let source_hash = lock::source_hash(&project.verifier_dir());
 
-    if let Some(exit_code) = output.status.code() {
-        if exit_code == 0 {
-            println!("{}", String::from_utf8_lossy(&output.stdout));
-        } else {
-            panic!("Failed to build project");
+    match command.status() {
+        Ok(status) if status.success() => log::info!("Verifier built"),
+        Ok(status) => {
+            return Err(CeresError::toolchain(format!("Failed to build project: cargo exited with {}", status)))
+        }
+        Err(err) => return Err(CeresError::toolchain(format!("Failed to run cargo: {}", err))),
+    }
+
+    let target_dir = out_dir.join("target");
+    let elf_path =
+        find_built_guest_elf(&out_dir, project_name, options.target.as_deref()).ok_or_else(guest_elf_not_found)?;
+    let image_id = image_id::compute(&elf_path)?;
+
+    // cargo puts cross-compiled binaries in target/<triple>/release
+    let release_dir = match &options.target {
+        Some(target) => target_dir.join(target).join("release"),
+        None => target_dir.join("release"),
+    };
+    let host_binary = release_dir.join(project_name);
+    fs::rename(release_dir.join("host"), &host_binary).context("Failed to rename executable")?;
+
+    let build_lock = lock::Lock {
+        name: project_name.to_string(),
+        image_id,
+        elf_sha256: lock::file_sha256(&elf_path)?,
+        source_hash,
+        toolchain: lock::toolchain(),
+        target: options.target.clone(),
+        host_binary: host_binary.strip_prefix(project.root()).unwrap_or(&host_binary).to_path_buf(),
+    };
+    lock::write(project.root(), &build_lock)?;
+
+    let static_binary = options.target.as_ref().and_then(|_| is_static_elf(&fs::read(&host_binary).ok()?));
+    if static_binary == Some(false) {
+        log::warn!("{} is dynamically linked, it may not run on other Linux distributions", host_binary.display());
+    }
+
+    Ok(BuildResult {
+        lock: build_lock,
+        elf_path,
+        host_binary,
+        static_binary,
+    })
+}
+
+/// Sets up `cargo build` to cross-compile the host for `target` and link it statically.
+/// The flags are passed for the target only, so they don't reach build scripts or the guest build.
+fn configure_target(command: &mut Command, target: &str) -> Result<()> {
+    if let Some(installed) = lock::command_output("rustup", &["target", "list", "--installed"]) {
+        if !installed.lines().any(|line| line.trim() == target) {
+            return Err(CeresError::toolchain(format!("The Rust target {} is not installed", target))
+                .with_hint(format!("Run \"rustup target add {}\"", target)));
         }
     }
 
-    println!("Please save the IMAGE ID. You will need it when submitting the collector-verifier to Mercury");
+    let env_target = target.to_uppercase().replace(['-', '.'], "_");
+    command
+        .arg("--target")
+        .arg(target)
+        .env(format!("CARGO_TARGET_{}_RUSTFLAGS", env_target), "-C target-feature=+crt-static");
+
+    // cross-compiling for another architecture needs its linker, which distributions name <arch>-linux-gnu-gcc
+    let linker_var = format!("CARGO_TARGET_{}_LINKER", env_target);
+    let arch = target.split('-').next().unwrap_or_default();
+    if env::var_os(&linker_var).is_none() && target.ends_with("-linux-gnu") && arch != env::consts::ARCH {
+        let linker = format!("{}-linux-gnu-gcc", arch);
+        if lock::command_output(&linker, &["--version"]).is_some() {
+            command.env(&linker_var, &linker);
+        } else {
+            log::warn!("{} not found, set {} to the linker for {}", linker, linker_var, target);
+        }
+    }
+    Ok(())
+}
 
-    let old_name = "target/release/host";
-    let new_name = format!("target/release/{}", project_name);
-    fs::rename(old_name, new_name).expect("failed to rename executable");
-    println!("Data verifier executable is located at ./verifier/out/{}/target/release/{}", project_name, project_name);
+/// Whether a 64-bit little-endian ELF executable has no program interpreter, i.e. is statically linked.
+/// None if the file is not such an ELF.
+fn is_static_elf(bytes: &[u8]) -> Option<bool> {
+    const PT_INTERP: u32 = 3;
+    if bytes.get(0..4)? != b"\x7fELF" || bytes.get(4)? != &2 || bytes.get(5)? != &1 {
+        return None;
+    }
+    let read_u16 = |offset: usize| Some(u16::from_le_bytes(bytes.get(offset..offset + 2)?.try_into().ok()?));
+    let read_u32 = |offset: usize| Some(u32::from_le_bytes(bytes.get(offset..offset + 4)?.try_into().ok()?));
+    let read_u64 = |offset: usize| Some(u64::from_le_bytes(bytes.get(offset..offset + 8)?.try_into().ok()?));
 
+    let program_headers = read_u64(0x20)? as usize;
+    let entry_size = read_u16(0x36)? as usize;
+    let entries = read_u16(0x38)? as usize;
+    for index in 0..entries {
+        if read_u32(program_headers + index * entry_size)? == PT_INTERP {
+            return Some(false);
+        }
+    }
+    Some(true)
 }
 
-fn get_pwd_name() -> String {
-    match env::current_dir() {
-        Ok(current_dir) => {
-            return String::from(current_dir.to_str().unwrap());
+/// Builds the guest twice in clean, isolated target directories and compares the results.
+/// A buyer can only trust a registry image ID if the submitted source reproduces it.
+/// The guest is built in risc0's Docker image, which pins the toolchain and the build paths, so both builds and anyone
+/// else building the same source get the same ELF. The target directories are removed afterwards.
+pub fn check_reproducible(project: &Project, options: &BuildOptions) -> Result<ReproducibilityReport> {
+    let project_name = project.name();
+    let out_dir = project.out_dir();
+
+    if lock::command_output("docker", &["--version"]).is_none() {
+        return Err(CeresError::environment("Docker is not installed or not running")
+            .with_hint("Reproducible builds run the guest build in risc0's Docker image. Install Docker and start it"));
+    }
+    ensure_generated(project, options)?;
+
+    let locked = out_dir.join("Cargo.lock").exists();
+    if !locked {
+        log::warn!("No Cargo.lock in the generated project, dependency versions are not pinned");
+    }
+    let lock_before = fs::read_to_string(out_dir.join("Cargo.lock")).unwrap_or_default();
+
+    let mut builds: Vec<ReproducibleBuild> = Vec::new();
+    let mut elf_bytes: Vec<Vec<u8>> = Vec::new();
+    let mut build_paths: Vec<String> = Vec::new();
+    for run in 1..=2 {
+        let target_dir = tempfile::Builder::new()
+            .prefix(&format!("ceres-repro-{}-{}-", project_name, run))
+            .tempdir()
+            .context("Failed to create a reproducibility target directory")?;
+        log::info!("Build {} of 2 in {}", run, target_dir.path().display());
+
+        let mut command = Command::new("cargo");
+        command.current_dir(&out_dir).arg("build").arg("--release");
+        if locked {
+            command.arg("--locked");
+        }
+        command
+            .env("CARGO_TARGET_DIR", target_dir.path())
+            .env("CARGO_INCREMENTAL", "0")
+            .env("SOURCE_DATE_EPOCH", "0")
+            .env("RISC0_USE_DOCKER", "1");
+
+        match command.status() {
+            Ok(status) if status.success() => {}
+            _ => return Err(CeresError::toolchain(format!("Reproducibility build {} failed", run))),
         }
-        Err(_) => {
-            return String::from("");
+
+        let elf_path = find_guest_elf(&target_dir.path().join("riscv-guest"), project_name)
+            .ok_or_else(guest_elf_not_found)?;
+        let bytes = fs::read(&elf_path).context("Failed to read the guest ELF")?;
+        builds.push(ReproducibleBuild {
+            elf_sha256: lock::file_sha256(&elf_path)?,
+            image_id: image_id::compute(&elf_path)?,
+        });
+        // the target directory is removed when it goes out of scope, so only its path is kept for the comparison
+        build_paths.push(target_dir.path().to_string_lossy().to_string());
+        elf_bytes.push(bytes);
+    }
+
+    let reproducible = builds[0].elf_sha256 == builds[1].elf_sha256
+        && builds[0].image_id == builds[1].image_id
+        && elf_bytes[0] == elf_bytes[1];
+
+    let causes = if reproducible {
+        Vec::new()
+    } else {
+        let lock_after = fs::read_to_string(out_dir.join("Cargo.lock")).unwrap_or_default();
+        build_paths.push(out_dir.to_string_lossy().to_string());
+        divergence_causes([&elf_bytes[0], &elf_bytes[1]], &build_paths, lock_before != lock_after)
+    };
+
+    Ok(ReproducibilityReport {
+        builds,
+        reproducible,
+        causes,
+        lock_image_id: lock::read(project.root()).map(|build_lock| build_lock.image_id),
+    })
+}
+
+/// Makes sure verifier/out/<name> exists and was generated from the current verifier source, so the image ID matches it.
+fn ensure_generated(project: &Project, options: &BuildOptions) -> Result<()> {
+    let out_dir = project.out_dir();
+    if !out_dir.exists() {
+        return Err(CeresError::environment(format!("Generated project not found at {}", out_dir.display()))
+            .with_hint("Run \"ceres gen\" first"));
+    }
+    if !gen::is_stale(project) {
+        return Ok(());
+    }
+
+    if options.no_regen {
+        return Err(CeresError::environment(format!(
+            "{} was not generated from the current verifier source",
+            out_dir.display()
+        ))
+        .with_hint("Run \"ceres dev\" to re-sync it, or build without --no-regen"));
+    }
+    log::info!("The verifier source changed since {} was generated, re-syncing it", out_dir.display());
+    for change in gen::sync(project)? {
+        log::debug!("Updated {}", change.path.display());
+    }
+    Ok(())
+}
+
+/// Guesses why two builds of the same source produced different ELFs. Each cause is listed once.
+fn divergence_causes(elfs: [&[u8]; 2], build_paths: &[String], lock_changed: bool) -> Vec<String> {
+    let mut causes: Vec<String> = Vec::new();
+    if lock_changed {
+        causes.push(String::from("dependency versions: Cargo.lock changed during the build. Commit a Cargo.lock and pin dependency versions"));
+    }
+    if elfs.iter().any(|elf| build_paths.iter().any(|path| contains(elf, path.as_bytes()))) {
+        causes.push(String::from("build paths: a build directory is embedded in the ELF, check that the guest is built in Docker"));
+    }
+    if causes.is_empty() && elfs[0].len() == elfs[1].len() {
+        causes.push(String::from("build timestamps or other non-deterministic build script output: the ELFs have the same size but different content"));
+    } else if causes.is_empty() {
+        causes.push(String::from("toolchain or build environment differences: check that both builds used the same risc0 Docker image"));
+    }
+    causes
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    !needle.is_empty() && haystack.windows(needle.len()).any(|window| window == needle)
+}
+
+fn guest_elf_not_found() -> CeresError {
+    CeresError::toolchain("Could not find the guest ELF in target/riscv-guest")
+        .with_hint("Check that the methods crate builds the guest, and that its package name matches the project name")
+}
+
+/// risc0-build places the guest ELF somewhere under target/riscv-guest, named after the guest package.
+/// risc0 2.x writes the program binary the image ID is computed from next to it, with a `.bin` extension.
+pub fn find_guest_elf(dir: &Path, name: &str) -> Option<PathBuf> {
+    find_file(dir, name).or_else(|| find_file(dir, &format!("{}.bin", name)))
+}
+
+/// The guest ELF of a build in the generated project `out_dir`. With `--target`, risc0-build may put it under
+/// target/<triple>/riscv-guest instead.
+pub fn find_built_guest_elf(out_dir: &Path, name: &str, target: Option<&str>) -> Option<PathBuf> {
+    let target_dir = out_dir.join("target");
+    find_guest_elf(&target_dir.join("riscv-guest"), name)
+        .or_else(|| find_guest_elf(&target_dir.join(target?).join("riscv-guest"), name))
+}
+
+fn find_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    for entry in fs::read_dir(dir).ok()?.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            if let Some(found) = find_file(&path, name) {
+                return Some(found);
+            }
+        } else if path.file_name().is_some_and(|file_name| file_name == name) {
+            return Some(path);
         }
     }
-}
\ No newline at end of file
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths() -> Vec<String> {
+        vec![String::from("/tmp/ceres-repro-a-1-x"), String::from("/tmp/ceres-repro-a-2-y"), String::from("/p/verifier/out/a")]
+    }
+
+    #[test]
+    fn embedded_build_paths_are_listed_once() {
+        let first = b"elf /tmp/ceres-repro-a-1-x/riscv-guest".to_vec();
+        let second = b"elf /tmp/ceres-repro-a-2-y/riscv-guest /p/verifier/out/a".to_vec();
+        let causes = divergence_causes([&first, &second], &paths(), false);
+        assert_eq!(causes.len(), 1);
+        assert!(causes[0].starts_with("build paths"));
+    }
+
+    #[test]
+    fn a_changed_lock_is_listed_with_the_paths() {
+        let first = b"elf /p/verifier/out/a".to_vec();
+        let second = b"elf".to_vec();
+        let causes = divergence_causes([&first, &second], &paths(), true);
+        assert_eq!(causes.len(), 2);
+        assert!(causes[0].starts_with("dependency versions"));
+        assert!(causes[1].starts_with("build paths"));
+    }
+
+    #[test]
+    fn the_elf_size_separates_timestamps_from_the_toolchain() {
+        let causes = divergence_causes([b"elf 0001", b"elf 0002"], &paths(), false);
+        assert_eq!(causes.len(), 1);
+        assert!(causes[0].starts_with("build timestamps"));
+
+        let causes = divergence_causes([b"elf 0001", b"elf 00002"], &paths(), false);
+        assert_eq!(causes.len(), 1);
+        assert!(causes[0].starts_with("toolchain"));
+    }
+}
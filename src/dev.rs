@@ -0,0 +1,234 @@
+/*
+The development loop behind `ceres dev`. Every cycle:
+- re-syncs verifier/out/<name> with the verifier folder, rewriting only the files that changed
+- runs the native tests of the generated project, with the guest build skipped
+- with --execute, runs every file in verifier/samples through the host in risc0 dev mode, which executes the guest without proving,
+  and writes the receipts to verifier/out/<name>/dev-receipts
+
+With --watch the cycle runs again whenever verifier/src, verifier/Cargo.toml, the samples or one of the other files gen reads change.
+*/
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    error::{CeresError, Result},
+    gen, lock,
+    preview::FileChange,
+    project::Project,
+};
+
+/// Sample data files, in the verifier folder.
+pub const SAMPLES_DIR: &str = "samples";
+/// The dev mode receipts written by --execute, in the generated project.
+pub const RECEIPTS_DIR: &str = "dev-receipts";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DevOptions {
+    /// Run the samples through the host in dev mode after the tests pass.
+    pub execute: bool,
+}
+
+#[derive(Debug)]
+pub struct DevReport {
+    /// The generated files that were created or modified by this cycle.
+    pub synced: Vec<FileChange>,
+    pub tests: Outcome,
+    /// Empty unless the samples were executed.
+    pub samples: Vec<SampleOutcome>,
+    pub duration: Duration,
+}
+
+#[derive(Debug)]
+pub struct Outcome {
+    pub passed: bool,
+    /// "3 passed", or the end of the cargo output when it failed.
+    pub detail: String,
+}
+
+#[derive(Debug)]
+pub struct SampleOutcome {
+    pub sample: PathBuf,
+    pub passed: bool,
+    /// The CID the host printed, or why the execution failed.
+    pub detail: String,
+    pub cid: Option<String>,
+    /// The dev mode receipt the host wrote, in verifier/out/<name>/dev-receipts.
+    pub receipt: Option<PathBuf>,
+}
+
+impl DevReport {
+    pub fn passed(&self) -> bool {
+        self.tests.passed && self.samples.iter().all(|sample| sample.passed)
+    }
+}
+
+/// Runs one cycle of the development loop.
+pub fn run(project: &Project, options: &DevOptions) -> Result<DevReport> {
+    let started = Instant::now();
+    let synced = gen::sync(project)?;
+    let out_dir = project.out_dir();
+
+    // risc0-build skips the guest build with RISC0_SKIP_BUILD, the native tests don't need it
+    let mut command = Command::new("cargo");
+    command.current_dir(&out_dir).arg("test").env("RISC0_SKIP_BUILD", "1");
+    let tests = match command.output() {
+        Ok(output) => test_outcome(&output),
+        Err(err) => return Err(CeresError::toolchain(format!("Failed to run cargo: {}", err))),
+    };
+
+    let mut samples = Vec::new();
+    if options.execute && tests.passed {
+        // cleared first, so the folder only holds the receipts of this run
+        let receipts_dir = out_dir.join(RECEIPTS_DIR);
+        if receipts_dir.exists() {
+            fs::remove_dir_all(&receipts_dir).map_err(|err| {
+                CeresError::io(format!("Failed to remove {}: {}", receipts_dir.display(), err))
+            })?;
+        }
+        for sample in sample_files(project) {
+            samples.push(execute_sample(&out_dir, &sample, &receipts_dir)?);
+        }
+    }
+
+    Ok(DevReport {
+        synced,
+        tests,
+        samples,
+        duration: started.elapsed(),
+    })
+}
+
+/// Runs a cycle, then another one every time a watched file changes, passing the changed files and the result to `on_cycle`.
+/// Only returns if `on_cycle` returns false.
+pub fn watch(
+    project: &Project,
+    options: &DevOptions,
+    mut on_cycle: impl FnMut(&[PathBuf], Result<DevReport>) -> bool,
+) {
+    let mut snapshot = watched_files(project);
+    let mut changed = Vec::new();
+    loop {
+        if !on_cycle(&changed, run(project, options)) {
+            return;
+        }
+
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let current = watched_files(project);
+            changed = changed_files(&snapshot, &current);
+            snapshot = current;
+            if !changed.is_empty() {
+                break;
+            }
+        }
+    }
+}
+
+/// The files in verifier/samples, sorted by name.
+pub fn sample_files(project: &Project) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    lock::collect_files(&project.verifier_dir().join(SAMPLES_DIR), &mut files);
+    files.sort();
+    files
+}
+
+/// The watched files and their modification time and size.
+fn watched_files(project: &Project) -> BTreeMap<PathBuf, (SystemTime, u64)> {
+    let verifier_dir = project.verifier_dir();
+    let mut files = lock::source_files(&verifier_dir);
+    lock::collect_files(&verifier_dir.join(SAMPLES_DIR), &mut files);
+
+    files
+        .into_iter()
+        .filter_map(|file| {
+            let metadata = fs::metadata(&file).ok()?;
+            Some((file, (metadata.modified().ok()?, metadata.len())))
+        })
+        .collect()
+}
+
+fn changed_files(
+    before: &BTreeMap<PathBuf, (SystemTime, u64)>,
+    after: &BTreeMap<PathBuf, (SystemTime, u64)>,
+) -> Vec<PathBuf> {
+    let mut changed: Vec<PathBuf> = after
+        .iter()
+        .filter(|(file, state)| before.get(*file) != Some(state))
+        .map(|(file, _)| file.clone())
+        .collect();
+    changed.extend(before.keys().filter(|file| !after.contains_key(*file)).cloned());
+    changed.sort();
+    changed
+}
+
+fn test_outcome(output: &Output) -> Outcome {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !output.status.success() {
+        return Outcome {
+            passed: false,
+            detail: last_lines(&format!("{}{}", stdout, String::from_utf8_lossy(&output.stderr)), 15),
+        };
+    }
+
+    // "test result: ok. 3 passed; 0 failed; ..." for every test binary
+    let passed: usize = stdout
+        .lines()
+        .filter_map(|line| line.strip_prefix("test result: ok. "))
+        .filter_map(|rest| rest.split_once(" passed"))
+        .filter_map(|(count, _)| count.parse::<usize>().ok())
+        .sum();
+    Outcome {
+        passed: true,
+        detail: format!("{} passed", passed),
+    }
+}
+
+fn execute_sample(out_dir: &Path, sample: &Path, receipts_dir: &Path) -> Result<SampleOutcome> {
+    let mut command = Command::new("cargo");
+    command
+        .current_dir(out_dir)
+        .args(["run", "--release", "--quiet", "--bin", "host", "--"])
+        // the sample first: hostlib::prepare(args: Vec<String>) reads it from args[1]
+        .arg(sample)
+        .arg(format!("--output-dir={}", receipts_dir.display()))
+        .env("RISC0_DEV_MODE", "1");
+
+    fs::create_dir_all(receipts_dir).map_err(|err| {
+        CeresError::io(format!("Failed to create {}: {}", receipts_dir.display(), err))
+    })?;
+    let output = command
+        .output()
+        .map_err(|err| CeresError::toolchain(format!("Failed to run cargo: {}", err)))?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let cid = stdout.lines().find_map(|line| line.strip_prefix("Verified data with CID: "));
+    Ok(match (output.status.success(), cid) {
+        (true, Some(cid)) => SampleOutcome {
+            sample: sample.to_path_buf(),
+            passed: true,
+            detail: format!("CID {}", cid),
+            cid: Some(cid.to_string()),
+            receipt: Some(receipts_dir.join(format!("{}.bin", cid))).filter(|receipt| receipt.exists()),
+        },
+        _ => SampleOutcome {
+            sample: sample.to_path_buf(),
+            passed: false,
+            detail: last_lines(&String::from_utf8_lossy(&output.stderr), 5),
+            cid: None,
+            receipt: None,
+        },
+    })
+}
+
+fn last_lines(text: &str, count: usize) -> String {
+    let lines: Vec<&str> = text.lines().filter(|line| !line.trim().is_empty()).collect();
+    lines[lines.len().saturating_sub(count)..].join("\n")
+}
@@ -0,0 +1,136 @@
+/*
+Checks that the environment can generate and build verifiers, printing pass/fail and a remediation for every check.
+*/
+use std::path::Path;
+
+use crate::{gen, lock::command_output};
+
+/// risc0 builds download and compile a lot of code, warn below this much free space.
+const MIN_FREE_SPACE_GB: u64 = 5;
+
+#[derive(Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub passed: bool,
+    pub detail: String,
+    pub remediation: String,
+}
+
+/// Runs every check. `dir` is the project (or any) directory whose disk space is checked.
+pub fn run_checks(dir: &Path) -> Vec<Check> {
+    vec![
+        check_cargo(),
+        check_rustc(),
+        check_cargo_risczero(),
+        check_risc0_toolchain(),
+        check_disk_space(dir),
+    ]
+}
+
+fn check_cargo() -> Check {
+    let version = command_output("cargo", &["--version"]);
+    Check {
+        name: "cargo",
+        passed: version.is_some(),
+        detail: version.unwrap_or_else(|| String::from("not found")),
+        remediation: String::from("Install Rust and cargo from https://rustup.rs"),
+    }
+}
+
+fn check_rustc() -> Check {
+    let version = command_output("rustc", &["--version"]);
+    Check {
+        name: "rustc",
+        passed: version.is_some(),
+        detail: version.unwrap_or_else(|| String::from("not found")),
+        remediation: String::from("Install Rust from https://rustup.rs"),
+    }
+}
+
+/// cargo-risczero generates the templates ceres builds on, so its major version must match the one ceres supports.
+fn check_cargo_risczero() -> Check {
+    let remediation = format!(
+        "Run \"cargo install cargo-risczero --version ^{}\"",
+        gen::SUPPORTED_RISC0_MAJOR
+    );
+    let version = match command_output("cargo", &["risczero", "--version"]) {
+        Some(version) => version,
+        None => {
+            return Check {
+                name: "cargo-risczero",
+                passed: false,
+                detail: String::from("not installed"),
+                remediation,
+            }
+        }
+    };
+
+    let major = version
+        .split_whitespace()
+        .last()
+        .and_then(|number| number.split('.').next())
+        .and_then(|major| major.parse::<u64>().ok());
+
+    match major {
+        Some(major) if major == gen::SUPPORTED_RISC0_MAJOR => Check {
+            name: "cargo-risczero",
+            passed: true,
+            detail: version,
+            remediation,
+        },
+        _ => Check {
+            name: "cargo-risczero",
+            passed: false,
+            detail: format!(
+                "{} is not compatible with the templates ceres generates (risc0 {}.x)",
+                version,
+                gen::SUPPORTED_RISC0_MAJOR
+            ),
+            remediation,
+        },
+    }
+}
+
+fn check_risc0_toolchain() -> Check {
+    let toolchains = command_output("rustup", &["toolchain", "list"]).unwrap_or_default();
+    let installed = toolchains.lines().any(|line| line.starts_with("risc0"));
+    Check {
+        name: "risc0 toolchain",
+        passed: installed,
+        detail: if installed {
+            String::from("installed")
+        } else {
+            String::from("the risc0 rust toolchain is not installed")
+        },
+        remediation: String::from("Run \"cargo risczero install\""),
+    }
+}
+
+fn check_disk_space(dir: &Path) -> Check {
+    let out_dir = dir.join("verifier").join("out");
+    let target_dir = if out_dir.exists() { out_dir.as_path() } else { dir };
+
+    match free_space_gb(target_dir) {
+        Some(free) => Check {
+            name: "disk space",
+            passed: free >= MIN_FREE_SPACE_GB,
+            detail: format!("{} GB free in {}", free, target_dir.display()),
+            remediation: format!(
+                "Free up at least {} GB, or run \"cargo clean\" in old verifier projects",
+                MIN_FREE_SPACE_GB
+            ),
+        },
+        None => Check {
+            name: "disk space",
+            passed: false,
+            detail: format!("could not determine free space in {}", target_dir.display()),
+            remediation: String::from("Make sure the df command is available"),
+        },
+    }
+}
+
+fn free_space_gb(dir: &Path) -> Option<u64> {
+    let output = command_output("df", &["-Pk", dir.to_str()?])?;
+    let available_kb: u64 = output.lines().nth(1)?.split_whitespace().nth(3)?.parse().ok()?;
+    Some(available_kb / 1024 / 1024)
+}
@@ -0,0 +1,115 @@
+/*
+Every subcommand returns a Result<_, CeresError>. main prints the message and hint and exits with the code of the error kind,
+so scripts wrapping ceres can tell failures apart.
+
+Exit codes:
+- 0: success
+- 2: user input (invalid arguments or answers)
+- 3: environment (not a ceres project, missing files or tools)
+- 4: IO (reading or writing files failed)
+- 5: template (a generated or user file does not have the expected structure)
+- 6: toolchain (cargo, cargo-risczero or the risc0 build failed)
+- 7: network (downloading code failed)
+*/
+use std::{fmt, io};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    UserInput,
+    Environment,
+    Io,
+    Template,
+    Toolchain,
+    Network,
+}
+
+impl ErrorKind {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorKind::UserInput => 2,
+            ErrorKind::Environment => 3,
+            ErrorKind::Io => 4,
+            ErrorKind::Template => 5,
+            ErrorKind::Toolchain => 6,
+            ErrorKind::Network => 7,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CeresError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub hint: Option<String>,
+}
+
+pub type Result<T> = std::result::Result<T, CeresError>;
+
+impl CeresError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        CeresError { kind, message: message.into(), hint: None }
+    }
+
+    pub fn user_input(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::UserInput, message)
+    }
+
+    pub fn environment(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Environment, message)
+    }
+
+    pub fn io(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Io, message)
+    }
+
+    pub fn template(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Template, message)
+    }
+
+    pub fn toolchain(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Toolchain, message)
+    }
+
+    pub fn network(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::Network, message)
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        self.kind.exit_code()
+    }
+}
+
+impl fmt::Display for CeresError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(hint) = &self.hint {
+            write!(f, "\nhint: {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CeresError {}
+
+impl From<reqwest::Error> for CeresError {
+    fn from(err: reqwest::Error) -> Self {
+        CeresError::network(format!("Network request failed: {}", err))
+            .with_hint("Check your internet connection and try again")
+    }
+}
+
+/// Turns an io::Result into a Result<_, CeresError> with a message saying what was being done.
+pub trait IoContext<T> {
+    fn context(self, message: impl fmt::Display) -> Result<T>;
+}
+
+impl<T> IoContext<T> for io::Result<T> {
+    fn context(self, message: impl fmt::Display) -> Result<T> {
+        self.map_err(|err| CeresError::io(format!("{}: {}", message, err)))
+    }
+}
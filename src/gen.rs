@@ -1,230 +1,387 @@
-use core::panic;
-use execute::Execute;
 use std::{
-    env, fs,
-    fs::OpenOptions,
-    io::BufWriter,
-    io::Write,
+    collections::BTreeMap,
+    fs,
     path::{Path, PathBuf},
     process::Command,
 };
+
+use crate::{
+    error::{CeresError, IoContext, Result},
+    lock,
+    preview::{self, ChangeKind, FileChange},
+    params,
+    project::Project,
+    schema, signing,
+};
+
+/// Major version of risc0 (cargo-risczero and risc0-zkvm) that the generated templates and image ID computation target.
+pub const SUPPORTED_RISC0_MAJOR: u64 = 2;
+
+/// Where the ceres-guest and ceres-host helper crates, added to the dependencies of every generated project, are fetched from.
+pub const CERES_GIT: &str = "https://github.com/mercury-protocol/ceres";
+/// The release tag of this version of ceres, so generated projects use the helper crates this version generates code for.
+pub const CERES_TAG: &str = concat!("v", env!("CARGO_PKG_VERSION"));
+
+/// Written into verifier/out/<name>: the `lock::source_hash` of the verifier folder the project was generated from.
+pub const FINGERPRINT_FILE: &str = ".ceres-fingerprint";
+
+#[derive(Debug)]
+pub struct GenResult {
+    /// The generated RiscZero project, verifier/out/<name>.
+    pub out_dir: PathBuf,
+}
+
 /*
 This function takes the code from the host and the guest and generate a fully working RiscZero program with the predefined code in the host/guest.
 */
-pub fn gen() {
+pub fn gen(project: &Project) -> Result<GenResult> {
     // 1. check that cargo-risczero is installed
-    let is_risczero_installed = check_risczero_install();
-    println!("Risc 0 installed: {:?}", is_risczero_installed);
-
-    if !is_risczero_installed {
-        panic!("Run cargo install cargo-risczero")
-    }
+    ensure_risczero_installed()?;
 
     // 2. create an out directory
-    // check that verifier dir exists, if not we're not in a Ceres project
-    let dir_path = Path::new("verifier");
-    if !dir_path.exists() || !dir_path.is_dir() {
-        panic!("This does not seem to be a Ceres project directory");
-    }
+    let out_dir_path = project.verifier_dir().join("out");
+    fs::create_dir(&out_dir_path).map_err(|err| {
+        CeresError::io(format!("Error creating folder {}: {}", out_dir_path.display(), err))
+            .with_hint("Delete verifier/out to generate the project again")
+    })?;
+    log::info!("Created out folder");
+
+    // hashed first, so an edit made while generating leaves the project stale
+    let fingerprint = lock::source_hash(&project.verifier_dir());
+    generate(project, &out_dir_path)?;
+    write_fingerprint(project, &fingerprint)?;
+
+    Ok(GenResult { out_dir: project.out_dir() })
+}
 
-    let out_dir_path = dir_path.join("out");
-    match fs::create_dir(&out_dir_path) {
-        Ok(_) => println!("Created out folder"),
-        Err(err) => panic!("Error creating folder: {}", err),
-    }
+/// Whether verifier/out/<name> was generated from a different source than the verifier folder holds now,
+/// or by a version of ceres that didn't record it.
+pub fn is_stale(project: &Project) -> bool {
+    let recorded = fs::read_to_string(project.out_dir().join(FINGERPRINT_FILE)).unwrap_or_default();
+    recorded.trim() != lock::source_hash(&project.verifier_dir())
+}
 
-    // create a risczero project
-    let pwd: String = get_pwd_name();
-    let substrings: Vec<&str> = pwd.split('/').collect();
-    let project_name = substrings.last().unwrap();
+fn write_fingerprint(project: &Project, fingerprint: &str) -> Result<()> {
+    let path = project.out_dir().join(FINGERPRINT_FILE);
+    fs::write(&path, fingerprint).context(format!("Failed to write {}", path.display()))
+}
+
+/// Generates the project in a temporary folder and compares it with verifier/out/<name>, without touching the project.
+pub fn preview(project: &Project) -> Result<Vec<FileChange>> {
+    ensure_risczero_installed()?;
+    staged(project, |staging_dir| {
+        preview::compare_dirs(project.root(), &staging_dir.join(project.name()), &project.out_dir())
+    })
+}
+
+/// Brings verifier/out/<name> up to date with the verifier folder, writing only the files that changed so the
+/// target folder and incremental builds are kept. Returns the files that were created or modified.
+pub fn sync(project: &Project) -> Result<Vec<FileChange>> {
+    ensure_risczero_installed()?;
+    let fingerprint = lock::source_hash(&project.verifier_dir());
+    staged(project, |staging_dir| {
+        let staged_project = staging_dir.join(project.name());
+        let changes: Vec<FileChange> =
+            preview::compare_dirs(project.root(), &staged_project, &project.out_dir())?
+                .into_iter()
+                .filter(|change| change.kind != ChangeKind::Unchanged)
+                .collect();
+
+        for change in &changes {
+            let relative = change.path.strip_prefix(project.out_dir()).unwrap_or(&change.path);
+            if let Some(parent) = change.path.parent() {
+                fs::create_dir_all(parent).context(format!("Failed to create {}", parent.display()))?;
+            }
+            fs::copy(staged_project.join(relative), &change.path)
+                .context(format!("Failed to write {}", change.path.display()))?;
+        }
+        write_fingerprint(project, &fingerprint)?;
+        Ok(changes)
+    })
+}
+
+/// Runs `f` on a temporary folder the project has been generated in, and removes the folder afterwards.
+fn staged<T>(project: &Project, f: impl FnOnce(&Path) -> Result<T>) -> Result<T> {
+    // a fresh folder per call, so concurrent calls for the same project don't share one
+    let staging_dir = tempfile::Builder::new()
+        .prefix(&format!("ceres-staging-{}-", project.name()))
+        .tempdir()
+        .context("Failed to create staging folder")?;
 
-    println!("Project name: {}", project_name);
+    generate(project, staging_dir.path()).and_then(|_| f(staging_dir.path()))
+}
+
+fn ensure_risczero_installed() -> Result<()> {
+    let is_risczero_installed = check_risczero_install()?;
+    log::debug!("cargo-risczero installed: {}", is_risczero_installed);
 
-    if let Err(err) = env::set_current_dir(&out_dir_path) {
-        eprintln!("Failed to change working directory: {}", err);
+    if !is_risczero_installed {
+        return Err(CeresError::environment("cargo-risczero is not installed")
+            .with_hint("Run \"ceres doctor\" to check your environment"));
     }
+    Ok(())
+}
+
+/// Creates the RiscZero project in `out_dir_path`/<name> and fills it with the verifier code.
+fn generate(project: &Project, out_dir_path: &Path) -> Result<()> {
+    let dir_path = project.verifier_dir();
+
+    // create a risczero project
+    let project_name = project.name();
+
+    log::debug!("Project name: {}", project_name);
 
     let mut command = Command::new("cargo");
 
-    command.arg("risczero").arg("new").arg(project_name);
+    command.current_dir(out_dir_path).arg("risczero").arg("new").arg(project_name);
 
-    if command.execute_check_exit_status_code(0).is_err() {
-        panic!("Failed to create risczero project")
+    match command.output() {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => {
+            return Err(CeresError::toolchain(format!(
+                "Failed to create risczero project: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )))
+        }
+        Err(err) => return Err(CeresError::toolchain(format!("Failed to run cargo risczero: {}", err))),
     }
 
     // 3. update the Cargo.toml files with the correct name, from the main project dir name
-    println!("Risc zero created");
-
-    match fs::create_dir(Path::new(project_name).join("sample")) {
-        Ok(_) => println!("Created sample folder"),
-        Err(err) => panic!("Error creating folder: {}", err),
-    }
+    log::debug!("RiscZero project created in {}", out_dir_path.display());
+    let out_dir = out_dir_path.join(project_name);
 
-    let file_path = Path::new(project_name).join("methods").join("Cargo.toml");
+    fs::create_dir(out_dir.join("sample")).context("Error creating sample folder")?;
+    log::debug!("Created sample folder");
 
-    let modified_name = format!("name = \"{}-methods\"", project_name);
+    let file_path = out_dir.join("methods").join("Cargo.toml");
 
-    change_line(&file_path, 1, modified_name.as_str());
+    set_package_name(&file_path, &format!("{}-methods", project_name))?;
 
-    let host_cargo_file_path = Path::new(project_name).join("host").join("Cargo.toml");
-    update_host_method_import(&host_cargo_file_path, &project_name);
+    let host_cargo_file_path = out_dir.join("host").join("Cargo.toml");
+    update_host_method_import(&host_cargo_file_path, project_name)?;
 
-    let gitignore_path = Path::new(project_name).join(".gitignore");
-    let mut file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .open(gitignore_path)
-        .unwrap();
-    file.write_all(".prinfo".as_bytes()).expect("failed to write to .gitignore");
-    
     // - methods/guest/Cargo.toml --> name = "<project-name>"
-    let file_guest_path = Path::new(project_name)
+    let file_guest_path = out_dir
     .join("methods")
     .join("guest")
     .join("Cargo.toml");
 
-    let modified_content = format!("name = \"{}\"", project_name);
-    
-    change_line(&file_guest_path, 2, modified_content.as_str());
-    use_std(&file_guest_path);
-    
-    // - host/src/main.rs --> raname use <project_name>-methods::{PROJECT_NAME-ELF, PROJECT_NAME_ID}
-    let file_host_path = Path::new(project_name)
-        .join("host")
-        .join("src")
-        .join("main.rs");
-
-    let modified_host_line = format!(
-        "use {}_methods::{{{}_ELF, {}_ID}};",
-        project_name,
-        project_name.to_uppercase(),
-        project_name.to_uppercase()
-    );
-
-    change_line(&file_host_path, 3, modified_host_line.as_str());
+    set_package_name(&file_guest_path, project_name)?;
+    use_std(&file_guest_path)?;
 
     // 4. check the dependencies installed by the user -> install them to out
-    let current_dir = env::current_dir().unwrap();
-    let parent_dir = current_dir.parent().expect("Parent directory not found");
-
-    if let Err(err) = env::set_current_dir(parent_dir) {
-        eprintln!("Failed to change working directory: {}", err);
-    }
-
-    let installed_packages = get_installed_packages(Path::new("Cargo.toml"));
+    let collector_key = signing::public_key(project)?;
+    let data_schema = schema::read(project)?;
+    let verifier_params = params::read(project)?;
+    let features = Features {
+        collector_key: collector_key.as_ref(),
+        schema: data_schema.as_ref(),
+        typed_prepare: uses_context(&dir_path.join("src").join("hostlib.rs"))?,
+        params: verifier_params.as_ref(),
+        config: read_config(project)?,
+    };
+    let installed_packages = get_installed_packages(&dir_path.join("Cargo.toml"), &features)?;
     add_installed_packages(
-        &Path::new("out")
-            .join(project_name)
+        &out_dir
             .join("host")
             .join("Cargo.toml"),
         installed_packages.host,
-    );
+    )?;
     add_installed_packages(
-        &Path::new("out")
-            .join(project_name)
+        &out_dir
             .join("methods")
             .join("guest")
             .join("Cargo.toml"),
         installed_packages.guest,
-    );
+    )?;
+
+    // the generated params modules derive serde traits, and the host reads the parameters as JSON
+    if features.params.is_some() {
+        let host_cargo_path = out_dir.join("host").join("Cargo.toml");
+        enable_serde_derive(&host_cargo_path)?;
+        enable_serde_derive(&out_dir.join("methods").join("guest").join("Cargo.toml"))?;
+        if !has_dependency(&read_lines(&host_cargo_path)?, "serde_json") {
+            add_installed_packages(&host_cargo_path, vec!["serde_json = \"1.0\"".to_string()])?;
+        }
+    }
 
     // 5. Add premade code to host & guest
-    prepare_guest_host_code(project_name);
+    prepare_guest_host_code(project_name, &out_dir, &features)?;
     // 6. Add code from the user to host & guest
-    add_guest_host_code(project_name);
+    add_guest_host_code(project, &out_dir, &features)
 }
 
-fn check_risczero_install() -> bool {
-    let crate_name = "cargo-risczero";
-    let output = Command::new("cargo")
-        .arg("install")
-        .arg("--list")
-        .output()
-        .expect("Failed to check for cargo-risczero tool. Is cargo installed?");
-
-    let installed_crates = String::from_utf8_lossy(&output.stdout);
+/// Optional parts of the generated project, enabled by files in the verifier folder.
+struct Features<'a> {
+    /// verifier/collector.pub: the guest only accepts data signed with this key.
+    collector_key: Option<&'a signing::Key>,
+    /// verifier/schema.json: guestlib.rs is generated from the schema.
+    schema: Option<&'a schema::Schema>,
+    /// hostlib::prepare takes a ceres_host::Context rather than the process arguments.
+    typed_prepare: bool,
+    /// verifier/config.json: values embedded in the host and passed to prepare in the context.
+    config: Vec<(String, String)>,
+    /// verifier/params.json: parameters the host passes to the guest, which commits them to the journal.
+    params: Option<&'a BTreeMap<String, params::Param>>,
+}
 
-    return installed_crates.contains(crate_name);
+/// A value the guest commits to the journal, and the host prints after proving.
+struct JournalEntry {
+    name: &'static str,
+    label: &'static str,
+    host_type: &'static str,
+    guest_value: &'static str,
 }
 
-fn get_pwd_name() -> String {
-    match env::current_dir() {
-        Ok(current_dir) => {
-            return String::from(current_dir.to_str().unwrap());
+impl Features<'_> {
+    /// The journal is the CID alone, or a tuple of the CID and the values of the enabled features, in this order.
+    fn journal(&self) -> Vec<JournalEntry> {
+        let mut entries = vec![JournalEntry {
+            name: "cid",
+            label: "Verified data with CID",
+            host_type: "String",
+            guest_value: "cid.to_string()",
+        }];
+        if self.collector_key.is_some() {
+            entries.push(JournalEntry {
+                name: "signer",
+                label: "Signed by collector key",
+                host_type: "String",
+                guest_value: "signed.public_key_hex()",
+            });
+        }
+        if self.schema.is_some() {
+            entries.push(JournalEntry {
+                name: "schema_hash",
+                label: "Schema hash",
+                host_type: "String",
+                guest_value: "guestlib::SCHEMA_HASH.to_string()",
+            });
         }
-        Err(_) => {
-            return String::from("");
+        if self.params.is_some() {
+            entries.push(JournalEntry {
+                name: "params",
+                label: "Verifier parameters",
+                host_type: "params::Params",
+                guest_value: "params::get().clone()",
+            });
         }
+        entries
     }
 }
 
-fn change_line(file_path: &PathBuf, line_num: usize, modified_line: &str) {
-    let file_content: Vec<String> = fs::read_to_string(file_path)
-        .unwrap()
-        .lines()
-        .map(|line| line.to_string())
-        .collect();
+/// Whether the `prepare` signature in hostlib.rs takes a Context. Older projects take `args: Vec<String>`.
+fn uses_context(hostlib_path: &Path) -> Result<bool> {
+    let content = fs::read_to_string(hostlib_path).context("Failed to read hostlib.rs")?;
+    Ok(content
+        .split_once("fn prepare(")
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .is_some_and(|(params, _)| params.contains("Context")))
+}
 
-    let modified_content: Vec<String> = file_content
-        .iter()
-        .enumerate()
-        .map(|(line_number, line)| {
-            if line_number == line_num {
-                modified_line.to_string()
-            } else {
-                line.to_string()
-            }
+/// The flat JSON object in verifier/config.json, with every value as a string.
+fn read_config(project: &Project) -> Result<Vec<(String, String)>> {
+    let path = project.verifier_dir().join("config.json");
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let invalid = |message: String| {
+        CeresError::template(format!("{} is not valid: {}", path.display(), message))
+            .with_hint("config.json must be an object of string, number or boolean values")
+    };
+    let content = fs::read_to_string(&path).context("Failed to read config.json")?;
+    let values: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(&content).map_err(|err| invalid(err.to_string()))?;
+
+    values
+        .into_iter()
+        .map(|(name, value)| match value {
+            serde_json::Value::String(value) => Ok((name, value)),
+            serde_json::Value::Number(_) | serde_json::Value::Bool(_) => Ok((name, value.to_string())),
+            _ => Err(invalid(format!("{} is not a string, number or boolean", name))),
         })
-        .collect();
+        .collect()
+}
+
+fn check_risczero_install() -> Result<bool> {
+    let crate_name = "cargo-risczero";
+    let output = Command::new("cargo")
+        .arg("install")
+        .arg("--list")
+        .output()
+        .map_err(|err| {
+            CeresError::environment(format!("Failed to check for cargo-risczero tool: {}", err))
+                .with_hint("Is cargo installed? Run \"ceres doctor\" to check your environment")
+        })?;
 
-    let file = fs::File::create(&file_path).unwrap();
-    let mut writer = BufWriter::new(file);
+    let installed_crates = String::from_utf8_lossy(&output.stdout);
 
-    // Write the modified content back to the file
-    for line in modified_content {
-        writeln!(writer, "{}", line).unwrap();
-    }
+    Ok(installed_crates.contains(crate_name))
 }
 
-fn use_std(file_path: &PathBuf) {
-    // packages.guest.push(line.replace("default-features = false }", "default-features = false, features = [ \"std\" ] }"));
-    let file_content: Vec<String> = fs::read_to_string(file_path)
-        .unwrap()
-        .lines()
-        .map(|line| line.to_string())
-        .collect();
-
-    let file = fs::File::create(&file_path).unwrap();
-    let mut writer = BufWriter::new(file);
+fn read_lines(file_path: &Path) -> Result<Vec<String>> {
+    let content = fs::read_to_string(file_path).context(format!("Failed to read {}", file_path.display()))?;
+    Ok(content.lines().map(|line| line.to_string()).collect())
+}
 
-    for line in file_content {
-        println!("Line contente: {}", line);
+fn write_lines(file_path: &Path, lines: &[String]) -> Result<()> {
+    let mut content = lines.join("\n");
+    content.push('\n');
+    fs::write(file_path, content).context(format!("Failed to write {}", file_path.display()))
+}
 
-        if line.contains("risc0-zkvm") {
-            writeln!(writer, "{}", line.replace("default-features = false", "default-features = false, features = [ \"std\" ]")).unwrap();
-        } else {
-            writeln!(writer, "{}", line).unwrap();
+/// Sets `name = ` in the [package] table of a Cargo.toml from the template.
+fn set_package_name(file_path: &Path, name: &str) -> Result<()> {
+    let mut file_content = read_lines(file_path)?;
+    let mut in_package = false;
+    let mut found = false;
+    for line in file_content.iter_mut() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_package = trimmed == "[package]";
+        } else if in_package && trimmed.split_once('=').is_some_and(|(key, _)| key.trim() == "name") {
+            *line = format!("name = \"{}\"", name);
+            found = true;
+            break;
         }
     }
+    if !found {
+        return Err(CeresError::template(format!("{} has no package name", file_path.display()))
+            .with_hint("The installed cargo-risczero generates a different template. Run \"ceres doctor\""));
+    }
+    write_lines(file_path, &file_content)
 }
 
-fn update_host_method_import(file_path: &Path, project_name: &str) {
-    let file_content: Vec<String> = fs::read_to_string(file_path)
-        .unwrap()
-        .lines()
-        .map(|line| line.to_string())
+fn use_std(file_path: &Path) -> Result<()> {
+    let file_content: Vec<String> = read_lines(file_path)?
+        .into_iter()
+        .map(|line| {
+            // the risc0 2.x template already enables std
+            if line.contains("risc0-zkvm") && !line.contains("features") {
+                line.replace("default-features = false", "default-features = false, features = [ \"std\" ]")
+            } else {
+                line
+            }
+        })
         .collect();
 
-    let file = fs::File::create(&file_path).unwrap();
-    let mut writer = BufWriter::new(file);
+    write_lines(file_path, &file_content)
+}
 
-    for line in file_content {
-        if line.contains("methods = ") {
-            writeln!(writer, "{}", line.replace("methods =", &format!("{}-methods =", project_name))).unwrap();
-        } else {
-            writeln!(writer, "{}", line).unwrap();
-        }
-    }
+fn update_host_method_import(file_path: &Path, project_name: &str) -> Result<()> {
+    let file_content: Vec<String> = read_lines(file_path)?
+        .into_iter()
+        .map(|line| {
+            if line.contains("methods = ") {
+                line.replace("methods =", &format!("{}-methods =", project_name))
+            } else {
+                line
+            }
+        })
+        .collect();
+
+    write_lines(file_path, &file_content)
 }
 
 #[derive(Default)]
@@ -233,22 +390,30 @@ struct Packages {
     pub guest: Vec<String>,
 }
 
-fn get_installed_packages(file_path: &Path) -> Packages {
-    let file_content: Vec<String> = fs::read_to_string(file_path)
-        .unwrap()
-        .lines()
-        .map(|line| line.to_string())
-        .collect();
+fn get_installed_packages(file_path: &Path, features: &Features) -> Result<Packages> {
+    let file_content = read_lines(file_path)?;
 
     let mut packages = Packages::default();
 
     let mut collecting_host: bool = false;
     let mut collecting_guest: bool = false;
+    let mut in_dependencies = false;
 
+    // only the [dependencies] table is split by the "# Host" and "# Guest" comment lines, other tables stay in the verifier
     for line in file_content {
-        if line.contains("Host") {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            in_dependencies = trimmed == "[dependencies]";
+            continue;
+        }
+        if !in_dependencies {
+            continue;
+        }
+        if trimmed.starts_with("# Host") {
             collecting_host = true;
-        } else if line.contains("Guest") {
+            collecting_guest = false;
+            continue;
+        } else if trimmed.starts_with("# Guest") {
             collecting_host = false;
             collecting_guest = true;
             continue;
@@ -260,6 +425,13 @@ fn get_installed_packages(file_path: &Path) -> Packages {
             packages.guest.push(line);
         }
     }
+    if !collecting_host && !collecting_guest {
+        return Err(CeresError::template(format!(
+            "{} is missing the Host and Guest dependency markers",
+            file_path.display()
+        ))
+        .with_hint("Keep the \"# Host\" and \"# Guest\" comment lines created by \"ceres init\""));
+    }
     if !packages.host.contains(&"bincode".to_string()) {
         packages.host.push("bincode = \"1.3.3\"".to_string());
     }
@@ -268,110 +440,128 @@ fn get_installed_packages(file_path: &Path) -> Packages {
         packages.guest.push("cid = \"0.7.0\"".to_string());
     }
 
-    return packages;
-}
+    // keep the user's version if they pinned one in the verifier Cargo.toml
+    if !packages.guest.iter().any(|line| line.trim_start().starts_with("ceres-guest")) {
+        let crate_features = match features.collector_key {
+            Some(key) => format!(", features = [\"{}\"]", key.scheme.name()),
+            None => String::new(),
+        };
+        packages.guest.push(format!("ceres-guest = {{ git = \"{}\", tag = \"{}\"{} }}", CERES_GIT, CERES_TAG, crate_features));
+    }
+    if !packages.host.iter().any(|line| line.trim_start().starts_with("ceres-host")) {
+        packages.host.push(ceres_host_dependency());
+    }
 
-fn add_installed_packages(file_path: &PathBuf, packages: Vec<String>) {
-    let mut file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .open(file_path)
-        .unwrap();
 
-    for package in packages {
-        file.write_all(format!("{}\n", package).as_bytes())
-            .expect("Failed to add package");
-    }
+    Ok(packages)
 }
 
-fn prepare_guest_host_code(project_name: &str) {
-    let host_file_path = &Path::new("out")
-        .join(project_name)
-        .join("host")
-        .join("src")
-        .join("main.rs");
-
-    let host_file_content: Vec<String> = fs::read_to_string(host_file_path)
-        .unwrap()
-        .lines()
-        .map(|line| line.to_string())
-        .collect();
+fn has_dependency(lines: &[String], name: &str) -> bool {
+    lines.iter().any(|line| line.split_once('=').is_some_and(|(key, _)| key.trim() == name))
+}
 
-    let host_file = fs::File::create(&host_file_path).unwrap();
-    let mut host_writer = BufWriter::new(host_file);
+/// Turns `serde = "<version>"` into a dependency with the derive feature, or adds serde if it is missing.
+fn enable_serde_derive(file_path: &Path) -> Result<()> {
+    let mut file_content = read_lines(file_path)?;
+    if !has_dependency(&file_content, "serde") {
+        file_content.push("serde = { version = \"1.0\", features = [\"derive\"] }".to_string());
+        return write_lines(file_path, &file_content);
+    }
 
-    let mut skip = false;
+    for line in file_content.iter_mut() {
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim();
+            if key.trim() == "serde" && value.starts_with('"') {
+                *line = format!("serde = {{ version = {}, features = [\"derive\"] }}", value);
+            }
+        }
+    }
+    write_lines(file_path, &file_content)
+}
 
-    // Write the modified content back to the file
-    for line in host_file_content {
-        if line.contains("fn main()") {
-            writeln!(host_writer, "{}", line).unwrap();
-            writeln!(host_writer, "{}", "    let args: Vec<String> = env::args().collect();").unwrap();
-            writeln!(host_writer, "{}", "    if args.len() > 1 && args[1] == \"get-img-id\" {").unwrap();
-            let get_img_id_cmd = format!("        let hex_string: String = {}_ID.iter().map(|&value| format!(\"{{:08X}}\", value)).collect(); // Use {{:08X}} for 8-digit uppercase hexadecimal.collect();\n        println!(\"GUEST IMAGE ID: 0x{{}}\", hex_string);\n        return;\n    }}\n", project_name.to_uppercase());
-            writeln!(host_writer, "{}", get_img_id_cmd).unwrap();
-            writeln!(host_writer, "{}", "    let data: Vec<u8> = hostlib::prepare(args);\n").unwrap();
-            writeln!(host_writer, "{}", "    let env = ExecutorEnv::builder().add_input(&to_vec(&data.as_slice()).unwrap()).build();\n").unwrap();
-            let exec_cmd = format!("    let mut exec = default_executor_from_elf(env, {}_ELF).unwrap();\n", project_name.to_uppercase());
-            writeln!(host_writer, "{}", exec_cmd).unwrap();
-            writeln!(host_writer, "{}", "    let session = exec.run().unwrap();").unwrap();
-            writeln!(host_writer, "{}", "    let receipt = session.prove().unwrap();").unwrap();
-            writeln!(host_writer, "{}", "    let cid: String = from_slice(&receipt.journal).unwrap();").unwrap();
-            writeln!(host_writer, "{}", "    println!(\"Verified data with CID: {}\", cid);\n").unwrap();
-            writeln!(host_writer, "{}", "    let encoded = bincode::serialize(&receipt).unwrap();\n").unwrap();
-            writeln!(host_writer, "{}", "    let file_path = format!(\"{}.bin\", cid);").unwrap();
-            writeln!(host_writer, "{}", "    let mut file = File::create(file_path).unwrap();").unwrap();
-            writeln!(host_writer, "{}", "    file.write_all(&encoded).expect(\"Failed to write encoded receipt\");").unwrap();
-            writeln!(host_writer, "{}", "    println!(\"serialized receipt written to file\");").unwrap();
+/// The ceres-host dependency line, also added to the Host section of new verifiers by `ceres init`.
+pub fn ceres_host_dependency() -> String {
+    format!("ceres-host = {{ git = \"{}\", tag = \"{}\" }}", CERES_GIT, CERES_TAG)
+}
 
-            skip = true;
-            continue;
+/// Adds dependency lines to the [dependencies] table of a generated Cargo.toml. A dependency the table already has is
+/// replaced, cargo rejects duplicates. Lines of other tables are never touched.
+fn add_installed_packages(file_path: &Path, packages: Vec<String>) -> Result<()> {
+    let mut file_content = read_lines(file_path)?;
+    let start = match file_content.iter().position(|line| line.trim() == "[dependencies]") {
+        Some(index) => index + 1,
+        None => {
+            file_content.push(String::from("[dependencies]"));
+            file_content.len()
         }
+    };
+    let mut end = file_content[start..]
+        .iter()
+        .position(|line| line.trim().starts_with('['))
+        .map_or(file_content.len(), |len| start + len);
 
-        if line.contains("}") {
-            skip = false;
-        }
-        
-        if skip {
-            continue;
+    for package in packages {
+        let name = package.split_once('=').map(|(name, _)| name.trim().to_string());
+        let existing = name.and_then(|name| {
+            file_content[start..end]
+                .iter()
+                .position(|line| line.split_once('=').is_some_and(|(key, _)| key.trim() == name))
+        });
+        match existing {
+            Some(index) => file_content[start + index] = package,
+            None => {
+                // after the last line of the table, before the blank lines separating it from the next one
+                let last = file_content[start..end].iter().rposition(|line| !line.trim().is_empty()).map_or(start, |index| start + index + 1);
+                file_content.insert(last, package);
+                end += 1;
+            }
         }
+    }
+    write_lines(file_path, &file_content)
+}
 
-        writeln!(host_writer, "{}", line).unwrap();
+/// With a collector key, the guest takes a signed envelope and verifies it. See `Features::journal` for what it commits.
+fn prepare_guest_host_code(project_name: &str, out_dir: &Path, features: &Features) -> Result<()> {
+    let collector_key = features.collector_key;
+    let journal = features.journal();
 
-        if line.contains("ELF") && line.contains("ID") {
-            writeln!(host_writer, "{}", "use std::env;").unwrap();
-            writeln!(host_writer, "{}", "mod hostlib;").unwrap();
-            writeln!(host_writer, "{}", "use std::fs::File;").unwrap();
-            writeln!(host_writer, "{}", "use std::io::Write;").unwrap();
-            writeln!(host_writer, "{}", "use bincode;").unwrap();
-        }
-    }
+    let host_file_path = out_dir.join("host").join("src").join("main.rs");
+    fs::write(&host_file_path, host_main(project_name, features)).context("Failed to write the host main.rs")?;
 
-    let guest_file_path = &Path::new("out")
-        .join(project_name)
+    let guest_file_path = &out_dir
         .join("methods")
         .join("guest")
         .join("src")
         .join("main.rs");
-    let guest_file_content: Vec<String> = fs::read_to_string(guest_file_path)
-        .unwrap()
-        .lines()
-        .map(|line| line.to_string())
-        .collect();
-
-    let guest_file = fs::File::create(&guest_file_path).unwrap();
-    let mut guest_writer = BufWriter::new(guest_file);
+    let guest_file_content = read_lines(guest_file_path)?;
+    let mut guest_lines: Vec<String> = Vec::new();
+    let mut skip = false;
 
     for line in guest_file_content {
         if line.contains("fn main()") {
-            writeln!(guest_writer, "{}", line).unwrap();
-            writeln!(guest_writer, "{}", "    let data: Vec<u8> = env::read();").unwrap();
-            writeln!(guest_writer, "{}", "    guestlib::verify(&data);\n").unwrap();
-            writeln!(guest_writer, "{}", "    const RAW: u64 = 0x55;").unwrap();
-            writeln!(guest_writer, "{}", "    let h = Code::Sha2_256.digest(&data);").unwrap();
-            writeln!(guest_writer, "{}", "    let cid = Cid::new_v1(RAW, h);").unwrap();
-            writeln!(guest_writer, "{}", "    env::commit(&cid.to_string());").unwrap();
-            writeln!(guest_writer, "{}", "    return ();").unwrap();
+            guest_lines.push(line.clone());
+            if collector_key.is_some() {
+                guest_lines.push("    let envelope: Vec<u8> = env::read();".to_string());
+                guest_lines.push("    let signed = signature::verify(&envelope, COLLECTOR_KEY_SCHEME, COLLECTOR_PUBLIC_KEY)".to_string());
+                guest_lines.push("        .expect(\"the data is not signed by the collector\");".to_string());
+                guest_lines.push("    let data: Vec<u8> = signed.payload.to_vec();".to_string());
+            } else {
+                guest_lines.push("    let data: Vec<u8> = env::read();".to_string());
+            }
+            if features.params.is_some() {
+                guest_lines.push("    params::set(env::read());".to_string());
+            }
+            guest_lines.push("    guestlib::verify(&data);\n".to_string());
+            guest_lines.push("    const RAW: u64 = 0x55;".to_string());
+            guest_lines.push("    let h = Code::Sha2_256.digest(&data);".to_string());
+            guest_lines.push("    let cid = Cid::new_v1(RAW, h);".to_string());
+            let values: Vec<&str> = journal.iter().map(|entry| entry.guest_value).collect();
+            if values.len() == 1 {
+                guest_lines.push(format!("    env::commit(&{});", values[0]));
+            } else {
+                guest_lines.push(format!("    env::commit(&({}));", values.join(", ")));
+            }
+            guest_lines.push("    return ();".to_string());
 
             skip = true;
             continue;
@@ -389,25 +579,208 @@ fn prepare_guest_host_code(project_name: &str) {
             continue;
         }
         
-        writeln!(guest_writer, "{}", line).unwrap();
+        guest_lines.push(line.clone());
 
         if line.contains("use risc0_zkvm::guest::env;") {
-            writeln!(guest_writer, "{}", "use cid::multihash::{Code, MultihashDigest};\nuse cid::Cid;").unwrap();
+            guest_lines.push("use cid::multihash::{Code, MultihashDigest};\nuse cid::Cid;".to_string());
+            if let Some(key) = collector_key {
+                let key_bytes: Vec<String> = key.bytes.iter().map(|byte| format!("0x{:02x}", byte)).collect();
+                guest_lines.push("use ceres_guest::signature::{self, Scheme};\n".to_string());
+                guest_lines.push(format!("const COLLECTOR_KEY_SCHEME: Scheme = Scheme::{:?};", key.scheme));
+                guest_lines.push(format!("const COLLECTOR_PUBLIC_KEY: &[u8] = &[{}];", key_bytes.join(", ")));
+            }
+            guest_lines.push("mod guestlib;".to_string());
+            if features.params.is_some() {
+                guest_lines.push("mod params;".to_string());
+            }
         }
+    }
+    write_lines(guest_file_path, &guest_lines)
+}
+
+/// The host main.rs, for the risc0 2.x API: prepares the data with hostlib, proves the guest with the default prover,
+/// prints the journal and writes the receipt to <output dir>/<cid>.bin.
+fn host_main(project_name: &str, features: &Features) -> String {
+    let journal = features.journal();
+    // risc0-build names the constants after the guest package, with dashes replaced
+    let methods = format!("{}_methods", project_name.replace('-', "_"));
+    let guest = project_name.replace('-', "_").to_uppercase();
+
+    let mut lines: Vec<String> = vec![
+        format!("use {}::{{{}_ELF, {}_ID}};", methods, guest, guest),
+        String::from("use risc0_zkvm::{default_prover, ExecutorEnv};"),
+        String::from("use std::env;"),
+        String::from("mod hostlib;"),
+    ];
+    if features.params.is_some() {
+        lines.push(String::from("mod params;"));
+    }
+    lines.push(String::from("use ceres_host::Context;"));
+    lines.push(String::from("use std::fs::File;"));
+    lines.push(String::from("use std::io::Write;"));
+    lines.push(String::new());
+
+    lines.push(String::from("fn main() {"));
+    lines.push(String::from("    let args: Vec<String> = env::args().collect();"));
+    lines.push(String::from("    if args.len() > 1 && args[1] == \"get-img-id\" {"));
+    lines.push(String::from("        // canonical digest form: the little-endian bytes of each word, in lowercase hex"));
+    lines.push(format!(
+        "        let hex_string: String = {}_ID.iter().flat_map(|value| value.to_le_bytes()).map(|byte| format!(\"{{:02x}}\", byte)).collect();",
+        guest
+    ));
+    lines.push(String::from("        println!(\"GUEST IMAGE ID: {}\", hex_string);"));
+    lines.push(String::from("        return;"));
+    lines.push(String::from("    }"));
+
+    let config: Vec<String> = features.config.iter().map(|(name, value)| format!("({:?}, {:?})", name, value)).collect();
+    if config.is_empty() {
+        lines.push(String::from("    let ctx = Context::from_args(args);"));
+    } else {
+        lines.push(format!("    let ctx = Context::from_args(args).with_config(&[{}]);", config.join(", ")));
+    }
+    if features.params.is_some() {
+        lines.push(String::from("    let params = params::Params::load(&ctx);"));
+    }
+    if features.typed_prepare {
+        lines.push(String::from("    let data: Vec<u8> = hostlib::prepare(&ctx);"));
+    } else {
+        lines.push(String::from("    let data: Vec<u8> = hostlib::prepare(ctx.args.clone());"));
+    }
+    lines.push(String::new());
+
+    lines.push(String::from("    let mut builder = ExecutorEnv::builder();"));
+    lines.push(String::from("    builder.write(&data).unwrap();"));
+    if features.params.is_some() {
+        lines.push(String::from("    builder.write(&params).unwrap();"));
+    }
+    lines.push(String::from("    let env = builder.build().unwrap();"));
+    lines.push(format!("    let receipt = default_prover().prove(env, {}_ELF).unwrap().receipt;", guest));
+    lines.push(format!("    receipt.verify({}_ID).unwrap();", guest));
+    lines.push(String::new());
+
+    if journal.len() == 1 {
+        lines.push(String::from("    let cid: String = receipt.journal.decode().unwrap();"));
+    } else {
+        let names: Vec<&str> = journal.iter().map(|entry| entry.name).collect();
+        let types: Vec<&str> = journal.iter().map(|entry| entry.host_type).collect();
+        lines.push(format!("    let ({}): ({}) = receipt.journal.decode().unwrap();", names.join(", "), types.join(", ")));
+    }
+    for entry in &journal {
+        let format = if entry.host_type == "String" { "{}" } else { "{:?}" };
+        lines.push(format!("    println!(\"{}: {}\", {});", entry.label, format, entry.name));
+    }
+    lines.push(String::new());
+
+    lines.push(String::from("    let encoded = bincode::serialize(&receipt).unwrap();"));
+    lines.push(String::from("    let file_path = ctx.output_dir.join(format!(\"{}.bin\", cid));"));
+    lines.push(String::from("    let mut file = File::create(file_path).unwrap();"));
+    lines.push(String::from("    file.write_all(&encoded).expect(\"Failed to write encoded receipt\");"));
+    lines.push(String::from("    println!(\"serialized receipt written to file\");"));
+    lines.push(String::from("}"));
+
+    let mut source = lines.join("\n");
+    source.push('\n');
+    source
+}
 
-        if line.contains("risc0_zkvm::guest::entry!(main);") {
-            writeln!(guest_writer, "{}", "mod guestlib;").unwrap();
+fn add_guest_host_code(project: &Project, out_dir: &Path, features: &Features) -> Result<()> {
+    let guestlib_path = project.verifier_dir().join("src").join("guestlib.rs");
+    let hostlib_path = project.verifier_dir().join("src").join("hostlib.rs");
+
+    let host_dest_path = out_dir.join("host").join("src").join("hostlib.rs");
+    let guest_dest_path = out_dir.join("methods").join("guest").join("src").join("guestlib.rs");
+
+    match features.schema {
+        Some(data_schema) => {
+            fs::write(&guest_dest_path, schema::guestlib_source(data_schema)?).context("Failed to write guestlib")?;
+            log::debug!("Generated guestlib.rs from verifier/{}", schema::SCHEMA_FILE);
+        }
+        None => {
+            fs::copy(guestlib_path, guest_dest_path).context("Failed to copy guestlib")?;
         }
     }
+    fs::copy(hostlib_path, host_dest_path).context("Failed to copy hostlib")?;
+
+    if let Some(verifier_params) = features.params {
+        fs::write(out_dir.join("host").join("src").join("params.rs"), params::host_source(verifier_params)?)
+            .context("Failed to write the host params module")?;
+        fs::write(
+            out_dir.join("methods").join("guest").join("src").join("params.rs"),
+            params::guest_source(verifier_params),
+        )
+        .context("Failed to write the guest params module")?;
+        log::debug!("Generated params.rs from verifier/{}", params::PARAMS_FILE);
+    }
+    Ok(())
 }
 
-fn add_guest_host_code(project_name: &str) {
-    let guestlib_path = "src/guestlib.rs";
-    let hostlib_path = "src/hostlib.rs";
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let host_dest_path = format!("out/{}/host/src/hostlib.rs", project_name);
-    let guest_dest_path = format!("out/{}/methods/guest/src/guestlib.rs", project_name);
+    fn add_to(manifest: &str, packages: &[&str]) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Cargo.toml");
+        fs::write(&path, manifest).unwrap();
+        add_installed_packages(&path, packages.iter().map(|package| package.to_string()).collect()).unwrap();
+        fs::read_to_string(&path).unwrap()
+    }
 
-    fs::copy(guestlib_path, guest_dest_path).expect("Failed to copy guestlib");
-    fs::copy(hostlib_path, host_dest_path).expect("Failed to copy hostlib");
+    #[test]
+    fn add_installed_packages_only_changes_the_dependencies_table() {
+        let manifest = "[package]\nname = \"host\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1.0\"\n\n[features]\nversion = []\nserde = []\n";
+        assert_eq!(
+            add_to(manifest, &["serde = { version = \"1.0\", features = [\"derive\"] }", "version = \"0.3\"", "hex = \"0.4\""]),
+            "[package]\nname = \"host\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = { version = \"1.0\", features = [\"derive\"] }\nversion = \"0.3\"\nhex = \"0.4\"\n\n[features]\nversion = []\nserde = []\n"
+        );
+    }
+
+    #[test]
+    fn get_installed_packages_pins_the_ceres_crates_to_this_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Cargo.toml");
+        fs::write(&path, "[package]\nname = \"verifier\"\n\n[dependencies]\n# Host\nserde = \"1.0\"\n# Guest\n").unwrap();
+        let features = Features { collector_key: None, schema: None, typed_prepare: false, config: Vec::new(), params: None };
+        let packages = get_installed_packages(&path, &features).unwrap();
+
+        let tag = format!("tag = \"v{}\"", env!("CARGO_PKG_VERSION"));
+        let guest = packages.guest.iter().find(|line| line.starts_with("ceres-guest")).unwrap();
+        let host = packages.host.iter().find(|line| line.starts_with("ceres-host")).unwrap();
+        assert!(guest.contains(CERES_GIT) && guest.contains(&tag), "{}", guest);
+        assert!(host.contains(CERES_GIT) && host.contains(&tag), "{}", host);
+    }
+
+    fn config(json: &str) -> Result<Vec<(String, String)>> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("verifier")).unwrap();
+        fs::write(dir.path().join("verifier").join("config.json"), json).unwrap();
+        read_config(&Project::open(dir.path()).unwrap())
+    }
+
+    #[test]
+    fn read_config_takes_every_value_as_a_string() {
+        let values = config(r#"{ "unit": "celsius", "stations": 3, "strict": true }"#).unwrap();
+        let expected = [("stations", "3"), ("strict", "true"), ("unit", "celsius")];
+        assert_eq!(values, expected.map(|(name, value)| (name.to_string(), value.to_string())));
+    }
+
+    #[test]
+    fn read_config_rejects_other_values_than_an_object() {
+        for json in ["[1, 2]", "\"celsius\"", "3", "{ \"unit\": "] {
+            let err = config(json).unwrap_err();
+            assert!(err.message.contains("config.json is not valid"), "{}", err.message);
+        }
+        for json in [r#"{ "units": ["celsius"] }"#, r#"{ "unit": { "name": "celsius" } }"#, r#"{ "unit": null }"#] {
+            let err = config(json).unwrap_err();
+            assert!(err.message.ends_with("is not a string, number or boolean"), "{}", err.message);
+        }
+    }
+
+    #[test]
+    fn add_installed_packages_creates_the_dependencies_table() {
+        assert_eq!(
+            add_to("[package]\nname = \"guest\"\n", &["hex = \"0.4\""]),
+            "[package]\nname = \"guest\"\n[dependencies]\nhex = \"0.4\"\n"
+        );
+    }
 }
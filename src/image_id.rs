@@ -0,0 +1,116 @@
+/*
+Image IDs are computed by ceres directly from the built guest program and always printed in the
+canonical form used by risc0 verifiers: the lowercase hex of the digest bytes.
+
+Older versions of ceres printed the `[u32; 8]` words with `{:08X}` behind a `0x` prefix. That form has the
+bytes of every word reversed, but it is still accepted on input and converted to the canonical form.
+*/
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{CeresError, IoContext, Result},
+    lock,
+};
+
+/// Computes the image ID of a guest program built by risc0-build.
+/// risc0 writes the combined user + kernel program next to the ELF with a `.bin` extension.
+pub fn compute(elf_path: &Path) -> Result<String> {
+    let binary_path = match elf_path.extension() {
+        Some(extension) if extension == "bin" => elf_path.to_path_buf(),
+        _ => PathBuf::from(format!("{}.bin", elf_path.display())),
+    };
+    let program_path = if binary_path.exists() { binary_path } else { elf_path.to_path_buf() };
+
+    let blob = fs::read(&program_path).context("Failed to read guest program")?;
+    match risc0_binfmt::compute_image_id(&blob) {
+        Ok(digest) => Ok(digest.to_string()),
+        Err(err) => Err(CeresError::toolchain(format!(
+            "Failed to compute image ID from {}: {}",
+            program_path.display(),
+            err
+        ))
+        .with_hint("Is the guest built with risc0 2.0 or later? Run \"ceres doctor\"")),
+    }
+}
+
+/// Canonical form of an image ID given as the `[u32; 8]` words generated by risc0-build.
+pub fn from_words(words: &[u32; 8]) -> String {
+    let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_le_bytes()).collect();
+    lock::to_hex(&bytes)
+}
+
+/// Parses an image ID in either the canonical digest form or the legacy `0x`-prefixed word form,
+/// returning the canonical form.
+pub fn normalize(input: &str) -> Option<String> {
+    let input = input.trim();
+
+    if let Some(legacy) = input.strip_prefix("0x").or_else(|| input.strip_prefix("0X")) {
+        if !is_hex_id(legacy) {
+            return None;
+        }
+        let mut words = [0u32; 8];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = u32::from_str_radix(&legacy[i * 8..i * 8 + 8], 16).ok()?;
+        }
+        return Some(from_words(&words));
+    }
+
+    if is_hex_id(input) {
+        Some(input.to_lowercase())
+    } else {
+        None
+    }
+}
+
+fn is_hex_id(value: &str) -> bool {
+    value.len() == 64 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: [u32; 8] =
+        [0x1A2B3C4D, 0x5E6F7081, 0x92A3B4C5, 0xD6E7F809, 0x10213243, 0x54657687, 0x98A9BACB, 0xDCEDFE0F];
+    const LEGACY: &str = "0x1A2B3C4D5E6F708192A3B4C5D6E7F809102132435465768798A9BACBDCEDFE0F";
+    const CANONICAL: &str = "4d3c2b1a81706f5ec5b4a39209f8e7d64332211087766554cbbaa9980ffeeddc";
+
+    #[test]
+    fn words_are_written_as_little_endian_bytes() {
+        assert_eq!(from_words(&WORDS), CANONICAL);
+        assert_eq!(from_words(&[0; 8]), "0".repeat(64));
+    }
+
+    #[test]
+    fn normalizes_both_forms_to_the_canonical_one() {
+        assert_eq!(normalize(LEGACY).as_deref(), Some(CANONICAL));
+        assert_eq!(normalize(CANONICAL).as_deref(), Some(CANONICAL));
+        assert_eq!(normalize(&format!("  {}\n", CANONICAL)).as_deref(), Some(CANONICAL));
+    }
+
+    #[test]
+    fn normalizes_mixed_case() {
+        assert_eq!(normalize(&CANONICAL.to_uppercase()).as_deref(), Some(CANONICAL));
+        assert_eq!(normalize(&LEGACY.to_lowercase()).as_deref(), Some(CANONICAL));
+        assert_eq!(normalize(&LEGACY.replacen("0x", "0X", 1)).as_deref(), Some(CANONICAL));
+    }
+
+    #[test]
+    fn rejects_invalid_lengths_and_characters() {
+        assert!(is_hex_id(CANONICAL));
+        assert!(!is_hex_id(&CANONICAL[1..]));
+        assert!(!is_hex_id(&format!("{}0", CANONICAL)));
+        assert!(!is_hex_id(&CANONICAL.replacen('4', "g", 1)));
+        assert!(!is_hex_id(""));
+
+        assert_eq!(normalize(""), None);
+        assert_eq!(normalize(&CANONICAL[2..]), None);
+        assert_eq!(normalize(&format!("0x{}", &LEGACY[3..])), None);
+        assert_eq!(normalize(&format!("{}ab", LEGACY)), None);
+        assert_eq!(normalize(&LEGACY.replacen('A', "z", 1)), None);
+        assert_eq!(normalize(&format!("0x+{}", &LEGACY[3..])), None);
+    }
+}
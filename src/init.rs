@@ -1,95 +1,93 @@
-use core::panic;
-use std::{env, fs, io::Write, path::{Path, PathBuf}};
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
 use serde::Deserialize;
-use serde_json;
 use reqwest::{self, header::{HeaderMap, HeaderValue, USER_AGENT}};
 
-#[derive(Debug, PartialEq, Eq)]
+use crate::{
+    error::{CeresError, IoContext, Result},
+    gen,
+    preview::{self, ChangeKind, FileChange},
+    project::Project,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CollectorLang {
     GO,
     JS,
     PY
 }
 
-pub async fn init(main_name: &String, lang: CollectorLang) {
-    match fs::create_dir(main_name) {
-        Ok(_) => println!("Created {} folder", main_name),
-        Err(err) => panic!("Error creating folder: {}", err),
-    }
-    let collector_dir_path = Path::new(main_name).join("collector");
-    
-    match fs::create_dir(&collector_dir_path) {
-        Ok(_) => println!("Created collector folder"),
-        Err(err) => panic!("Error creating folder: {}", err),
-    }
-    pull_code(lang, &collector_dir_path).await;
+pub async fn init(project: &Project, lang: CollectorLang) -> Result<()> {
+    let main_name = project.name();
+    fs::create_dir(project.root()).context(format!("Error creating folder {}", main_name))?;
+    log::info!("Created {} folder", main_name);
 
-    let verifier_dir_path = Path::new(main_name).join("verifier");
+    let collector_dir_path = project.root().join("collector");
+    fs::create_dir(&collector_dir_path).context("Error creating collector folder")?;
+    log::info!("Created collector folder");
 
-    match fs::create_dir(&verifier_dir_path) {
-        Ok(_) => println!("Created verifier folder"),
-        Err(err) => panic!("Error creating folder: {}", err),
-    }
+    pull_code(lang, &collector_dir_path).await?;
 
-    match fs::File::create(&verifier_dir_path.join("Cargo.toml")) {
-        Ok(mut file) => {
-            let data = format!(
-                "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n edition = \"2021\"\n\n[dependencies]\n# Host - install host dependencies here, don't delete this line\n\n# Guest - install guest dependencies here, don't delete this line",
-                main_name
-            );
-            match file.write_all(data.as_bytes()) {
-                Ok(_) => println!("File written"),
-                Err(err) => eprintln!("Error writing file: {}", err),
-            }
-        }
-        Err(err) => panic!("Error creating file: {}", err),
-    }
+    let verifier_dir_path = project.verifier_dir();
+    fs::create_dir(&verifier_dir_path).context("Error creating verifier folder")?;
+    log::info!("Created verifier folder");
 
-    match fs::File::create(&verifier_dir_path.join("README.md")) {
-        Ok(_) => println!("README.md file created"),
-        Err(err) => panic!("Error creating file: {}", err),
-    }
+    fs::create_dir(verifier_dir_path.join("src")).context("Error creating verifier src folder")?;
+    log::info!("Created verifier src folder");
 
-    match fs::create_dir(&verifier_dir_path.join("src")) {
-        Ok(_) => println!("Created verifier src folder"),
-        Err(err) => panic!("Error creating folder: {}", err),
+    for (path, data) in verifier_files(project) {
+        write_file(&path, &data)?;
     }
 
-    match fs::File::create(&verifier_dir_path.join("src").join("hostlib.rs")) {
-        Ok(mut file) => {
-            let data = format!(
-                "pub fn prepare(args: Vec<String>) -> Vec<u8> {{ \n //write your host code here \n }}"
-            );
-            match file.write_all(data.as_bytes()) {
-                Ok(_) => println!("File written"),
-                Err(err) => eprintln!("Error writing file: {}", err),
-            }
-        }
-        Err(err) => panic!("Error creating file: {}", err),
-    }
+    Ok(())
+}
 
-    match fs::File::create(&verifier_dir_path.join("src").join("guestlib.rs")) {
-        Ok(mut file) => {
-            let data =
-                format!("pub fn verify(data: &Vec<u8>) -> bool {{ \n //write your code here \n }}");
-            match file.write_all(data.as_bytes()) {
-                Ok(_) => println!("File written"),
-                Err(err) => eprintln!("Error writing file: {}", err),
-            }
-        }
-        Err(err) => panic!("Error creating file: {}", err),
+/// Lists what `init` would create for the project, with diffs of the manifest and main.rs, without touching anything.
+pub fn preview(project: &Project, lang: CollectorLang) -> Vec<FileChange> {
+    // the collector code is downloaded, so only its folder is listed
+    let collector_dir_path = project.root().join("collector").join(collector_folder(&lang));
+    let mut changes = vec![FileChange {
+        kind: if collector_dir_path.exists() { ChangeKind::Unchanged } else { ChangeKind::Create },
+        path: collector_dir_path,
+        diff: None,
+    }];
+    for (path, data) in verifier_files(project) {
+        changes.push(preview::compare(project.root(), &path, data.as_bytes()));
     }
+    changes
+}
 
-    match fs::File::create(&verifier_dir_path.join("src").join("main.rs")) {
-        Ok(mut file) => {
-            let data = format!("mod guestlib;\nmod hostlib;\n\nuse std::env;\n\nfn main() {{\n    let args: Vec<String> = env::args().collect();\n\n    //testing the host code\n    let file_bytes: Vec<u8> = hostlib::prepare(args);\n\n    //testing the guest code\n    let guest_verification_result: bool = guestlib::verify(&file_bytes);\n    println!(\"Guest verification result: {{:?}}\", guest_verification_result);\n}}");
-            match file.write_all(data.as_bytes()) {
-                Ok(_) => println!("File written"),
-                Err(err) => eprintln!("Error writing file: {}", err),
-            }
-        }
-        Err(err) => panic!("Error creating file: {}", err),
-    }
+/// The files of the empty verifier, with their contents.
+fn verifier_files(project: &Project) -> Vec<(PathBuf, String)> {
+    let verifier_dir_path = project.verifier_dir();
+    let src_dir_path = verifier_dir_path.join("src");
+
+    let manifest = format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\n edition = \"2021\"\n\n[dependencies]\n# Host - install host dependencies here, don't delete this line\n{}\n\n# Guest - install guest dependencies here, don't delete this line",
+        project.name(),
+        gen::ceres_host_dependency()
+    );
+    let hostlib = "use ceres_host::Context;\n\n// ctx has the input paths, the --name=value options, the values in config.json and the output folder\npub fn prepare(ctx: &Context) -> Vec<u8> { \n //write your host code here \n }";
+    let guestlib = "pub fn verify(data: &Vec<u8>) -> bool { \n //write your code here \n }";
+    let main = "mod guestlib;\nmod hostlib;\n\nuse ceres_host::Context;\nuse std::env;\n\nfn main() {\n    let ctx = Context::from_args(env::args().collect());\n\n    //testing the host code\n    let file_bytes: Vec<u8> = hostlib::prepare(&ctx);\n\n    //testing the guest code\n    let guest_verification_result: bool = guestlib::verify(&file_bytes);\n    println!(\"Guest verification result: {:?}\", guest_verification_result);\n}";
+
+    vec![
+        (verifier_dir_path.join("Cargo.toml"), manifest),
+        (verifier_dir_path.join("README.md"), String::new()),
+        (src_dir_path.join("hostlib.rs"), hostlib.to_string()),
+        (src_dir_path.join("guestlib.rs"), guestlib.to_string()),
+        (src_dir_path.join("main.rs"), main.to_string()),
+    ]
+}
+
+fn write_file(path: &Path, data: &str) -> Result<()> {
+    let mut file = fs::File::create(path).context(format!("Error creating {}", path.display()))?;
+    file.write_all(data.as_bytes()).context(format!("Error writing {}", path.display()))?;
+    log::debug!("Wrote {}", path.display());
+    Ok(())
 }
 
 #[derive(Debug, Deserialize)]
@@ -98,64 +96,57 @@ struct RepositoryContents {
     download_url: String,
 }
 
-async fn pull_code(lang: CollectorLang, path: &PathBuf) {
-    let current_dir = env::current_dir().unwrap();
-
-    if let Err(err) = env::set_current_dir(&path) {
-        eprintln!("Failed to change working directory: {}", err);
+fn collector_folder(lang: &CollectorLang) -> &'static str {
+    match lang {
+        CollectorLang::GO => "ceres-go",
+        CollectorLang::JS => "ceres-js",
+        CollectorLang::PY => "ceres-go",
     }
+}
 
+async fn pull_code(lang: CollectorLang, path: &Path) -> Result<()> {
     let repo_owner = "mercury-protocol";
     let repo_name = "ceres-p2p-helpers";
-    let folder_path: &str;
-
-    if lang == CollectorLang::GO {
-        folder_path = "ceres-go";
-    } else if lang == CollectorLang::JS {
-        folder_path = "ceres-js";
-    } else if lang == CollectorLang::PY {
-        folder_path = "ceres-go";
-    } else {
-        panic!("unimplemented lang")
-    }
+    let folder_path = collector_folder(&lang);
 
-    match fs::create_dir(folder_path) {
-        Ok(_) => {
-            if let Err(err) = env::set_current_dir(folder_path) {
-                eprintln!("Failed to change working directory: {}", err);
-            }
-        },
-        Err(err) => panic!("Error creating folder: {}", err),
-    }
+    let code_dir = path.join(folder_path);
+    fs::create_dir(&code_dir).context(format!("Error creating folder {}", folder_path))?;
 
     let mut headers = HeaderMap::new();
     headers.insert(USER_AGENT, HeaderValue::from_static("ceres/1.0"));
 
-
-    let client = reqwest::Client::builder().default_headers(headers).build().unwrap();
+    let client = reqwest::Client::builder().default_headers(headers).build()?;
 
     let url = format!(
         "https://api.github.com/repos/{}/{}/contents/{}",
         repo_owner, repo_name, folder_path
     );
 
-    let response = client.get(&url).send().await.expect("error getting code folder");
+    let response = client.get(&url).send().await?;
 
     if response.status().is_success() {
-        let json_str = response.text().await.unwrap();
-        let contents: Vec<RepositoryContents> = serde_json::from_str(&json_str).expect("failed to deserialize repo contents");
+        let json_str = response.text().await?;
+        let contents: Vec<RepositoryContents> = serde_json::from_str(&json_str).map_err(|err| {
+            CeresError::network(format!("Unexpected response listing the collector code: {}", err))
+        })?;
 
         for item in contents {
-            let file_response = client.get(&item.download_url).send().await.expect("failed to pull file");
+            let file_response = client.get(&item.download_url).send().await?;
             if file_response.status().is_success() {
-                let file_content = file_response.bytes().await.unwrap().to_vec();
-                let mut file = fs::File::create(&item.name).expect("failed to create file");
-                file.write_all(&file_content).expect("failed to write file content");
+                let file_content = file_response.bytes().await?.to_vec();
+                let mut file = fs::File::create(code_dir.join(&item.name))
+                    .context(format!("Failed to create {}", item.name))?;
+                file.write_all(&file_content).context(format!("Failed to write {}", item.name))?;
             }
         }
+    } else {
+        return Err(CeresError::network(format!(
+            "Failed to list the collector code at {}: {}",
+            url,
+            response.status()
+        ))
+        .with_hint("GitHub may be rate limiting requests, try again later"));
     }
 
-    if let Err(err) = env::set_current_dir(&current_dir) {
-        eprintln!("Failed to change working directory: {}", err);
-    }
-}
\ No newline at end of file
+    Ok(())
+}
@@ -0,0 +1,26 @@
+/*
+Ceres as a library: open or create a Project and run the same operations the CLI offers, getting structured results back.
+The ceres binary in main.rs is a thin wrapper that parses arguments and prints these results.
+*/
+pub mod build;
+pub mod dev;
+pub mod doctor;
+pub mod error;
+pub mod gen;
+pub mod image_id;
+pub mod init;
+pub mod lock;
+pub mod logging;
+pub mod package;
+pub mod params;
+pub mod pr;
+pub mod preview;
+pub mod project;
+pub mod registry;
+pub mod schema;
+pub mod signing;
+pub mod status;
+
+pub use error::{CeresError, ErrorKind, Result};
+pub use registry::add_to_registry;
+pub use project::Project;
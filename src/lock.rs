@@ -0,0 +1,221 @@
+/*
+The ceres.lock file records what the last `ceres build` produced: the guest image ID, a hash of the guest ELF,
+a hash of the verifier source it was built from, the toolchain versions used, and the target and path of the host binary.
+It lives in the project root, next to the verifier folder.
+*/
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    error::{CeresError, IoContext, Result},
+    pr,
+};
+
+pub const LOCK_FILE: &str = "ceres.lock";
+
+#[derive(Default, Serialize, Deserialize, Debug)]
+pub struct Lock {
+    pub name: String,
+    pub image_id: String,
+    pub elf_sha256: String,
+    pub source_hash: String,
+    pub toolchain: Toolchain,
+    /// The `--target` triple the host was built for, None for the native platform.
+    #[serde(default)]
+    pub target: Option<String>,
+    /// The host binary, relative to the project root. Empty in locks written before it was recorded.
+    #[serde(default)]
+    pub host_binary: PathBuf,
+}
+
+impl Lock {
+    /// The host binary of the build, in the project in `project_dir`.
+    pub fn host_binary_path(&self, project_dir: &Path) -> PathBuf {
+        if self.host_binary.as_os_str().is_empty() {
+            project_dir.join("verifier").join("out").join(&self.name).join("target").join("release").join(&self.name)
+        } else {
+            project_dir.join(&self.host_binary)
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize, Debug)]
+pub struct Toolchain {
+    pub cargo: String,
+    pub rustc: String,
+    pub cargo_risczero: String,
+}
+
+pub fn read(project_dir: &Path) -> Option<Lock> {
+    let content = fs::read_to_string(project_dir.join(LOCK_FILE)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+pub fn write(project_dir: &Path, lock: &Lock) -> Result<()> {
+    let serialized = serde_json::to_string_pretty(lock)
+        .map_err(|err| CeresError::io(format!("Failed to serialize {}: {}", LOCK_FILE, err)))?;
+    fs::write(project_dir.join(LOCK_FILE), serialized).context(format!("Failed to write {}", LOCK_FILE))
+}
+
+pub fn toolchain() -> Toolchain {
+    let version = |program: &str, args: &[&str]| {
+        command_output(program, args).unwrap_or_else(|| String::from("unknown"))
+    };
+    Toolchain {
+        cargo: version("cargo", &["--version"]),
+        rustc: version("rustc", &["--version"]),
+        cargo_risczero: version("cargo", &["risczero", "--version"]),
+    }
+}
+
+/// Trimmed stdout of a command, or None if it could not run or failed.
+pub fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        None
+    }
+}
+
+/// Files in the verifier folder, besides Cargo.toml and src/, that `ceres gen` reads when they exist.
+const GEN_INPUTS: &[&str] = &["schema.json", "params.json", "config.json", "collector.pub"];
+
+/// Everything the user controls in the verifier folder: Cargo.toml, the files in src/ and the optional gen inputs that exist.
+pub fn source_files(verifier_dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![verifier_dir.join("Cargo.toml")];
+    files.extend(GEN_INPUTS.iter().map(|input| verifier_dir.join(input)).filter(|input| input.exists()));
+    collect_files(&verifier_dir.join("src"), &mut files);
+    files.sort();
+    files
+}
+
+/// Hash of the `source_files` of the verifier folder.
+/// PR answers in the verifier Cargo.toml are left out, so editing them doesn't make the build stale.
+pub fn source_hash(verifier_dir: &Path) -> String {
+    let manifest_path = verifier_dir.join("Cargo.toml");
+    let mut hasher = Sha256::new();
+    for file in source_files(verifier_dir) {
+        let relative = file.strip_prefix(verifier_dir).unwrap_or(&file);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        if file == manifest_path {
+            hasher.update(manifest_without_answers(&file));
+        } else {
+            hasher.update(fs::read(&file).unwrap_or_default());
+        }
+    }
+    to_hex(&hasher.finalize())
+}
+
+/// The manifest without its `[package.metadata.ceres.pr]` table, or as it is when it isn't valid TOML.
+fn manifest_without_answers(path: &Path) -> Vec<u8> {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    let mut manifest: toml::Value = match toml::from_str(&content) {
+        Ok(manifest) => manifest,
+        Err(_) => return content.into_bytes(),
+    };
+    remove_table(&mut manifest, &pr::MANIFEST_ANSWERS);
+    toml::to_string(&manifest).map(String::into_bytes).unwrap_or_else(|_| content.into_bytes())
+}
+
+/// Removes the table at `path`, and the tables above it that are left empty, so the manifest hashes the same as before
+/// the table was added.
+fn remove_table(value: &mut toml::Value, path: &[&str]) {
+    let (key, rest) = match path.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    if let Some(table) = value.as_table_mut() {
+        if let Some(child) = table.get_mut(*key) {
+            remove_table(child, rest);
+            if rest.is_empty() || child.as_table().is_some_and(|child| child.is_empty()) {
+                table.remove(*key);
+            }
+        }
+    }
+}
+
+pub fn file_sha256(path: &Path) -> Result<String> {
+    let bytes = fs::read(path).context(format!("Failed to read {} for hashing", path.display()))?;
+    Ok(to_hex(&Sha256::digest(bytes)))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_hash_ignores_pr_answers_in_the_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = dir.path().join("Cargo.toml");
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src").join("guestlib.rs"), "pub fn verify() {}\n").unwrap();
+        let package = "[package]\nname = \"weather\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = \"1.0\"\n";
+
+        fs::write(&manifest, package).unwrap();
+        let hash = source_hash(dir.path());
+        fs::write(&manifest, format!("{}\n[package.metadata.ceres.pr]\nemail = \"a@example.com\"\n", package)).unwrap();
+        assert_eq!(source_hash(dir.path()), hash);
+        fs::write(&manifest, format!("{}\n[package.metadata.ceres.pr]\nemail = \"b@example.com\"\ndescription = \"\"\"\n[x](y)\n\"\"\"\n", package)).unwrap();
+        assert_eq!(source_hash(dir.path()), hash);
+
+        fs::write(&manifest, package.replace("1.0", "1.0.200")).unwrap();
+        assert_ne!(source_hash(dir.path()), hash);
+        fs::write(&manifest, format!("{}\n[package.metadata.ceres]\nother = 1\n", package)).unwrap();
+        assert_ne!(source_hash(dir.path()), hash);
+        fs::write(dir.path().join("src").join("guestlib.rs"), "pub fn verify() { }\n").unwrap();
+        fs::write(&manifest, package).unwrap();
+        assert_ne!(source_hash(dir.path()), hash);
+    }
+
+    #[test]
+    fn host_binary_is_read_from_the_lock() {
+        let lock: Lock = serde_json::from_str(
+            r#"{"name":"weather","image_id":"","elf_sha256":"","source_hash":"","toolchain":{"cargo":"","rustc":"","cargo_risczero":""},
+            "target":"x86_64-unknown-linux-musl","host_binary":"verifier/out/weather/target/x86_64-unknown-linux-musl/release/weather"}"#,
+        )
+        .unwrap();
+        assert_eq!(lock.target.as_deref(), Some("x86_64-unknown-linux-musl"));
+        assert_eq!(
+            lock.host_binary_path(Path::new("/project")),
+            Path::new("/project/verifier/out/weather/target/x86_64-unknown-linux-musl/release/weather")
+        );
+    }
+
+    #[test]
+    fn locks_without_a_host_binary_use_the_native_release_dir() {
+        let lock: Lock = serde_json::from_str(
+            r#"{"name":"weather","image_id":"","elf_sha256":"","source_hash":"","toolchain":{"cargo":"","rustc":"","cargo_risczero":""}}"#,
+        )
+        .unwrap();
+        assert_eq!(lock.target, None);
+        assert_eq!(
+            lock.host_binary_path(Path::new("/project")),
+            Path::new("/project/verifier/out/weather/target/release/weather")
+        );
+    }
+}
@@ -0,0 +1,48 @@
+/*
+The library reports progress through the `log` facade: info for the steps of a command, warn for problems that don't stop it,
+debug for details. The ceres binary installs this logger, which writes to stderr so stdout only carries the results,
+and sets the level from -q and -v. Programs using ceres as a library can install any other `log` implementation.
+*/
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+struct StderrLogger;
+
+static LOGGER: StderrLogger = StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match record.level() {
+            Level::Info => eprintln!("{}", record.args()),
+            Level::Error => eprintln!("error: {}", record.args()),
+            Level::Warn => eprintln!("warning: {}", record.args()),
+            Level::Debug => eprintln!("debug: {}", record.args()),
+            Level::Trace => eprintln!("trace: {}", record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// The level for -q (warnings and errors only), the default, -v (debug) and -vv (trace).
+pub fn level(quiet: bool, verbose: u8) -> LevelFilter {
+    match (quiet, verbose) {
+        (true, _) => LevelFilter::Warn,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
+
+/// Installs the stderr logger. Does nothing if a logger is already installed.
+pub fn init(level: LevelFilter) {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
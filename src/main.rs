@@ -1,15 +1,55 @@
-use clap::{arg, Arg, ArgAction, Command};
-use core::panic;
+use clap::{arg, Arg, ArgAction, ArgMatches, Command};
+use serde_json::{json, Value};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicBool, Ordering},
+};
 
-mod build;
-mod gen;
-mod init;
-mod pr;
+use ceres::{
+    build::BuildOptions,
+    dev::{self, DevOptions, DevReport},
+    doctor,
+    error::IoContext,
+    init, logging, pr,
+    preview::FileChange,
+    registry::{self, Collector},
+    signing::{self, Scheme},
+    status::{ProjectStatus, Step},
+    CeresError, Project, Result,
+};
+
+/// How the result of a command is printed: lines for people, or a single JSON object on stdout for CI and other tools.
+/// Progress and warnings are logged to stderr in both cases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Output {
+    Text,
+    Json,
+}
+
+/// Set once the JSON result has been printed, so a command that fails after printing its result doesn't print a second object.
+static RESULT_PRINTED: AtomicBool = AtomicBool::new(false);
 
 #[tokio::main]
 async fn main() {
     let matches = cli().get_matches();
+    logging::init(logging::level(matches.get_flag("quiet"), matches.get_count("verbose")));
+    let output = match matches.get_one::<String>("output").map(String::as_str) {
+        Some("json") => Output::Json,
+        _ => Output::Text,
+    };
 
+    if let Err(err) = run(&matches, output).await {
+        eprintln!("error: {}", err);
+        if output == Output::Json && !RESULT_PRINTED.load(Ordering::Relaxed) {
+            print_json(json!({ "ok": false, "error": error_json(&err) }));
+        }
+        process::exit(err.exit_code());
+    }
+}
+
+async fn run(matches: &ArgMatches, output: Output) -> Result<()> {
     match matches.subcommand() {
         Some(("init", sub_matches)) => {
             let folder_name = sub_matches.get_one::<String>("NAME").expect("required");
@@ -22,28 +62,497 @@ async fn main() {
             } else if sub_matches.get_flag("py") {
                 lang = init::CollectorLang::PY;
             } else {
-                panic!("No lang specified")
+                return Err(CeresError::user_input("No collector language specified")
+                    .with_hint("Pass one of --go, --js or --py"));
+            }
+
+            let root = project_dir(sub_matches)?.join(folder_name);
+            if sub_matches.get_flag("dry-run") {
+                print_changes(output, "init", &Project::preview_init(root, lang)?);
+                return Ok(());
             }
 
-            init::init(folder_name, lang).await;
+            let files: Vec<PathBuf> = Project::preview_init(&root, lang)?
+                .into_iter()
+                .map(|change| change.path)
+                .collect();
+            let project = Project::init(root, lang).await?;
+            match output {
+                Output::Json => print_json(json!({
+                    "ok": true,
+                    "command": "init",
+                    "name": project.name(),
+                    "root": project.root(),
+                    "created": files,
+                })),
+                Output::Text => println!("Project {} created at {}", project.name(), project.root().display()),
+            }
         }
-        Some(("gen", _)) => {
-            gen::gen();
+        Some(("gen", sub_matches)) => {
+            let project = open_project(sub_matches)?;
+            if sub_matches.get_flag("dry-run") {
+                print_changes(output, "gen", &project.preview_gen()?);
+                return Ok(());
+            }
+
+            let result = project.gen()?;
+            match output {
+                Output::Json => print_json(json!({ "ok": true, "command": "gen", "out_dir": result.out_dir })),
+                Output::Text => println!("RiscZero project generated at {}", result.out_dir.display()),
+            }
         }
-        Some(("build", _)) => {
-            build::build();
+        Some(("dev", sub_matches)) => {
+            let project = open_project(sub_matches)?;
+            let options = DevOptions {
+                execute: sub_matches.get_flag("execute"),
+            };
+
+            if !sub_matches.get_flag("watch") {
+                let report = project.dev(&options)?;
+                let error = (!report.passed()).then(|| CeresError::toolchain("The verifier tests or samples failed"));
+                match output {
+                    Output::Json => print_json(dev_report_json(&report, error.as_ref())),
+                    Output::Text => print_dev_report(&project, &report),
+                }
+                return error.map_or(Ok(()), Err);
+            }
+
+            // in watch mode, the JSON output is one object per cycle
+            log::info!(
+                "Watching verifier/src, verifier/Cargo.toml and verifier/{} for changes. Press Ctrl-C to stop",
+                dev::SAMPLES_DIR
+            );
+            project.watch(&options, |changed, result| {
+                if !changed.is_empty() {
+                    let names: Vec<String> = changed.iter().map(|file| relative(&project, file)).collect();
+                    log::info!("\nChanged: {}", names.join(", "));
+                }
+                match (result, output) {
+                    (Ok(report), Output::Json) => println!("{}", dev_report_json(&report, None)),
+                    (Ok(report), Output::Text) => print_dev_report(&project, &report),
+                    (Err(err), Output::Json) => println!("{}", json!({ "ok": false, "error": error_json(&err) })),
+                    (Err(err), Output::Text) => eprintln!("error: {}", err),
+                }
+                true
+            });
         }
-        Some(("new-pr", _)) => {
-            pr::new_pr();
+        Some(("build", sub_matches)) => {
+            let project = open_project(sub_matches)?;
+            let options = BuildOptions {
+                no_regen: sub_matches.get_flag("no-regen"),
+                target: sub_matches.get_one::<String>("target").cloned(),
+            };
+            if sub_matches.get_flag("check-reproducible") {
+                let report = project.check_reproducible(&options)?;
+                if output == Output::Json {
+                    let builds: Vec<Value> = report
+                        .builds
+                        .iter()
+                        .map(|build| json!({ "image_id": build.image_id, "elf_sha256": build.elf_sha256 }))
+                        .collect();
+                    let error = (!report.reproducible).then(|| CeresError::toolchain("The guest build is not reproducible"));
+                    print_json(json!({
+                        "ok": error.is_none(),
+                        "command": "build",
+                        "reproducible": report.reproducible,
+                        "builds": builds,
+                        "causes": report.causes,
+                        "lock_image_id": report.lock_image_id,
+                        "error": error.as_ref().map(error_json),
+                    }));
+                    return error.map_or(Ok(()), Err);
+                }
+
+                for (i, build) in report.builds.iter().enumerate() {
+                    println!("Build {}: image ID {} ELF sha256 {}", i + 1, build.image_id, build.elf_sha256);
+                }
+                if !report.reproducible {
+                    println!("The guest build is NOT reproducible. Likely causes:");
+                    for cause in &report.causes {
+                        println!("- {}", cause);
+                    }
+                    return Err(CeresError::toolchain("The guest build is not reproducible"));
+                }
+                println!("The guest build is reproducible");
+                if let Some(lock_image_id) = report.lock_image_id {
+                    if lock_image_id == report.builds[0].image_id {
+                        println!("The image ID matches the one recorded in {}", ceres::lock::LOCK_FILE);
+                    } else {
+                        log::warn!(
+                            "The image ID recorded in {} ({}) differs. It was built outside Docker or from an older source, run \"RISC0_USE_DOCKER=1 ceres build\" to record the reproducible one",
+                            ceres::lock::LOCK_FILE, lock_image_id
+                        );
+                    }
+                }
+            } else {
+                let result = project.build(&options)?;
+                match output {
+                    Output::Json => print_json(json!({
+                        "ok": true,
+                        "command": "build",
+                        "image_id": result.lock.image_id,
+                        "elf_sha256": result.lock.elf_sha256,
+                        "elf_path": result.elf_path,
+                        "host_binary": result.host_binary,
+                        "target": options.target,
+                        "static": result.static_binary,
+                        "lock_file": project.root().join(ceres::lock::LOCK_FILE),
+                    })),
+                    Output::Text => {
+                        println!("GUEST IMAGE ID: {}", result.lock.image_id);
+                        println!("Image ID recorded in {}", ceres::lock::LOCK_FILE);
+                        println!("Data verifier executable is located at {}", result.host_binary.display());
+                    }
+                }
+            }
+        }
+        Some(("package", sub_matches)) => {
+            let result = open_project(sub_matches)?.package()?;
+            match output {
+                Output::Json => print_json(json!({
+                    "ok": true,
+                    "command": "package",
+                    "version": result.version,
+                    "archive": result.archive,
+                    "archive_checksum": result.archive_checksum,
+                })),
+                Output::Text => println!("Verifier package written to {}", result.archive.display()),
+            }
+        }
+        Some(("doctor", sub_matches)) => {
+            let checks = doctor::run_checks(&project_dir(sub_matches)?);
+            let failed = checks.iter().filter(|check| !check.passed).count();
+            let error = (failed > 0).then(|| {
+                CeresError::environment(format!("{} of {} checks failed", failed, checks.len()))
+                    .with_hint("Apply the fixes above and run \"ceres doctor\" again")
+            });
+            if output == Output::Json {
+                let results: Vec<Value> = checks
+                    .iter()
+                    .map(|check| json!({ "name": check.name, "passed": check.passed, "detail": check.detail, "remediation": check.remediation }))
+                    .collect();
+                print_json(json!({
+                    "ok": error.is_none(),
+                    "command": "doctor",
+                    "checks": results,
+                    "error": error.as_ref().map(error_json),
+                }));
+                return error.map_or(Ok(()), Err);
+            }
+
+            for check in &checks {
+                if check.passed {
+                    println!("[PASS] {}: {}", check.name, check.detail);
+                } else {
+                    println!("[FAIL] {}: {}", check.name, check.detail);
+                    println!("       fix: {}", check.remediation);
+                }
+            }
+
+            if let Some(error) = error {
+                return Err(error);
+            }
+            println!("\nAll checks passed");
+        }
+        Some(("status", sub_matches)) => {
+            let project = open_project(sub_matches)?;
+            let status = project.status();
+            match output {
+                Output::Json => print_json(status_json(&project, &status)),
+                Output::Text => print_status(&project, &status),
+            }
+        }
+        Some(("keygen", sub_matches)) => {
+            let scheme = if sub_matches.get_flag("secp256k1") { Scheme::Secp256k1 } else { Scheme::Ed25519 };
+            let keys = open_project(sub_matches)?.keygen(scheme)?;
+            match output {
+                Output::Json => print_json(json!({
+                    "ok": true,
+                    "command": "keygen",
+                    "scheme": scheme.name(),
+                    "public_key": keys.public.to_hex(),
+                    "private_key_file": keys.private_key,
+                    "public_key_file": keys.public_key,
+                })),
+                Output::Text => {
+                    println!("Collector public key: {}:{}", scheme.name(), keys.public.to_hex());
+                    println!("Private key written to {}. Keep it secret and don't commit it", keys.private_key.display());
+                    println!("Public key written to {}. Run \"ceres gen\" to verify signatures in the guest", keys.public_key.display());
+                }
+            }
+        }
+        Some(("sign", sub_matches)) => {
+            let dir = project_dir(sub_matches)?;
+            let data_file = dir.join(sub_matches.get_one::<String>("FILE").expect("required"));
+            let key_file = match sub_matches.get_one::<String>("key") {
+                Some(key) => dir.join(key),
+                None => dir.join(signing::PRIVATE_KEY_FILE),
+            };
+            let signed_file = match sub_matches.get_one::<String>("out") {
+                Some(out) => dir.join(out),
+                None => PathBuf::from(format!("{}.signed", data_file.display())),
+            };
+            signing::sign_file(&key_file, &data_file, &signed_file)?;
+            match output {
+                Output::Json => print_json(json!({ "ok": true, "command": "sign", "signed_file": signed_file })),
+                Output::Text => println!("Signed data written to {}", signed_file.display()),
+            }
+        }
+        Some(("new-pr", sub_matches)) => {
+            let dir = project_dir(sub_matches)?;
+            let project = Project::open(&dir)?;
+            let mut options = pr::PrOptions {
+                file: sub_matches.get_one::<String>("from").map(|file| dir.join(file)),
+                no_input: sub_matches.get_flag("no-input"),
+                ..Default::default()
+            };
+            for (key, _) in pr::FIELDS {
+                if let Some(value) = sub_matches.get_one::<String>(key) {
+                    options.answers.set(key, value.clone());
+                }
+            }
+            let pr_info = pr::gather(&project, &options)?;
+            let files = project.prepare_pr(&pr_info)?;
+            if output == Output::Json {
+                print_json(json!({ "ok": true, "command": "new-pr", "pr_file": files.pr_file }));
+                return Ok(());
+            }
+
+            println!("Success! PR.md file generated. Now please follow the following steps: ");
+            println!("1. Run \"git clone https://github.com/mercury-protocol/mcy-data-collectors.git\"");
+            println!("2. Go into the cloned repo and run \"ceres add-pr <PATH TO YOUR PR.MD FILE OR PROJECT FOLDER>\"");
+            println!("3. Push your changes and submit a pull request where you paste in the contents of the PR.md file");
         }
         Some(("add-pr", sub_matches)) => {
-            let pr_file = sub_matches
-                .get_one::<String>("PR_FILE")
-                .expect("Please provide the path to the pr.md file for the collector verifier");
-            pr::add_pr(pr_file);
+            let pr_path = sub_matches.get_one::<String>("PR").expect("required");
+            let registry_dir = project_dir(sub_matches)?;
+            let update = ceres::add_to_registry(&registry_dir.join(pr_path), &registry_dir)?;
+            match output {
+                Output::Json => print_json(json!({
+                    "ok": true,
+                    "command": "add-pr",
+                    "index_file": update.index_file,
+                    "registry_file": update.list_file,
+                    "pr_text_file": update.pr_text_file,
+                    "id": update.id,
+                    "updated": update.updated,
+                })),
+                Output::Text if update.updated => {
+                    println!("Updated data collector {} in {} and {}", update.id, registry::INDEX_FILE, registry::LIST_FILE)
+                }
+                Output::Text => println!("Added data collector {} to {} and {}", update.id, registry::INDEX_FILE, registry::LIST_FILE),
+            }
+        }
+        Some(("registry", sub_matches)) => {
+            let registry_dir = project_dir(sub_matches)?;
+            let index = registry::read_index(&registry_dir)?;
+            match sub_matches.subcommand() {
+                Some(("list", _)) => print_collectors(output, "registry list", &index.collectors.iter().collect::<Vec<_>>()),
+                Some(("search", search_matches)) => {
+                    let query = search_matches.get_one::<String>("QUERY").expect("required");
+                    print_collectors(output, "registry search", &index.search(query));
+                }
+                Some(("show", show_matches)) => {
+                    let key = show_matches.get_one::<String>("COLLECTOR").expect("required");
+                    let collector = index.find(key).ok_or_else(|| {
+                        CeresError::user_input(format!("No collector {} in the registry", key))
+                            .with_hint("Run \"ceres registry list\" to see the IDs and names of the collectors")
+                    })?;
+                    match output {
+                        Output::Json => print_json(json!({ "ok": true, "command": "registry show", "collector": collector })),
+                        Output::Text => print_collector(collector),
+                    }
+                }
+                _ => unreachable!("clap requires a registry subcommand"),
+            }
+        }
+        Some((name, _)) => {
+            return Err(CeresError::user_input(format!("Unknown subcommand {}", name))
+                .with_hint("Run \"ceres --help\" to see the available commands"))
+        }
+        None => {
+            return Err(CeresError::user_input("No subcommand given")
+                .with_hint("Run \"ceres --help\" to see the available commands"))
+        }
+    }
+    Ok(())
+}
+
+/// The directory given with -C/--project-dir, or the working directory. Relative paths on the command line are resolved against it.
+fn project_dir(matches: &ArgMatches) -> Result<PathBuf> {
+    let current_dir = env::current_dir().context("Failed to read the working directory")?;
+    match matches.get_one::<String>("project-dir") {
+        Some(dir) => Ok(current_dir.join(dir)),
+        None => Ok(current_dir),
+    }
+}
+
+fn open_project(matches: &ArgMatches) -> Result<Project> {
+    Project::open(project_dir(matches)?)
+}
+
+fn print_json(result: Value) {
+    println!("{}", result);
+    RESULT_PRINTED.store(true, Ordering::Relaxed);
+}
+
+fn error_json(err: &CeresError) -> Value {
+    json!({
+        "kind": format!("{:?}", err.kind),
+        "message": err.message,
+        "hint": err.hint,
+        "exit_code": err.exit_code(),
+    })
+}
+
+fn relative(project: &Project, path: &Path) -> String {
+    path.strip_prefix(project.root()).unwrap_or(path).display().to_string()
+}
+
+fn print_changes(output: Output, command: &str, changes: &[FileChange]) {
+    if output == Output::Json {
+        let changes: Vec<Value> = changes
+            .iter()
+            .map(|change| json!({ "path": change.path, "kind": change.kind.to_string(), "diff": change.diff }))
+            .collect();
+        print_json(json!({ "ok": true, "command": command, "dry_run": true, "changes": changes }));
+        return;
+    }
+
+    for change in changes {
+        println!("{:<9} {}", change.kind, change.path.display());
+    }
+    for diff in changes.iter().filter_map(|change| change.diff.as_ref()) {
+        println!("\n{}", diff);
+    }
+    println!("Dry run, nothing was written");
+}
+
+fn step_json(step: &Step) -> Value {
+    json!({ "state": step.state.to_string(), "detail": step.detail })
+}
+
+fn status_json(project: &Project, status: &ProjectStatus) -> Value {
+    let receipts: Vec<Value> = status
+        .receipts
+        .iter()
+        .map(|receipt| json!({ "path": receipt.path, "stale": receipt.stale }))
+        .collect();
+    json!({
+        "ok": true,
+        "command": "status",
+        "name": project.name(),
+        "root": project.root(),
+        "generated": step_json(&status.generated),
+        "build": step_json(&status.build),
+        "image_id": status.image_id,
+        "pr": step_json(&status.pr),
+        "samples": status.samples,
+        "receipts": receipts,
+    })
+}
+
+fn print_status(project: &Project, status: &ProjectStatus) {
+    println!("Project {} at {}\n", project.name(), project.root().display());
+    for (name, step) in [("generated", &status.generated), ("build", &status.build), ("pr", &status.pr)] {
+        println!("{:<10} {:<11} {}", name, step.state, step.detail);
+    }
+
+    let samples: Vec<String> = status.samples.iter().map(|sample| relative(project, sample)).collect();
+    println!("{:<10} {}", "samples", if samples.is_empty() { String::from("none") } else { samples.join(", ") });
+    let receipts: Vec<String> = status
+        .receipts
+        .iter()
+        .map(|receipt| {
+            let path = relative(project, &receipt.path);
+            if receipt.stale { format!("{} (stale)", path) } else { path }
+        })
+        .collect();
+    println!("{:<10} {}", "receipts", if receipts.is_empty() { String::from("none") } else { receipts.join(", ") });
+}
+
+fn print_collectors(output: Output, command: &str, collectors: &[&Collector]) {
+    if output == Output::Json {
+        print_json(json!({ "ok": true, "command": command, "collectors": collectors }));
+        return;
+    }
+    if collectors.is_empty() {
+        println!("No collectors found");
+        return;
+    }
+    println!("{:>4}  {:<30} {:<10} IMAGE ID", "ID", "NAME", "VERSION");
+    for collector in collectors {
+        let image = collector.image_id();
+        println!(
+            "{:>4}  {:<30} {:<10} {}",
+            collector.id,
+            collector.name,
+            image.and_then(|image| image.version.as_deref()).unwrap_or("unknown"),
+            image.map(|image| image.image_id.as_str()).unwrap_or_default()
+        );
+    }
+}
+
+fn print_collector(collector: &Collector) {
+    println!("{} (ID {})\n", collector.name, collector.id);
+    println!("{}\n", collector.description);
+    println!("{:<12} {}", "source code", collector.source_code);
+    println!("{:<12} {}", "contact", if collector.contact.is_empty() { "unknown" } else { &collector.contact });
+    println!("{:<12} {}", "PR text", if collector.pr_text.is_empty() { "none" } else { &collector.pr_text });
+    println!("image IDs");
+    for image in collector.image_ids.iter().rev() {
+        println!("  {:<10} {}", image.version.as_deref().unwrap_or("unknown"), image.image_id);
+    }
+}
+
+fn dev_report_json(report: &DevReport, error: Option<&CeresError>) -> Value {
+    let samples: Vec<Value> = report
+        .samples
+        .iter()
+        .map(|sample| {
+            json!({
+                "sample": sample.sample,
+                "passed": sample.passed,
+                "cid": sample.cid,
+                "receipt": sample.receipt,
+                "detail": sample.detail,
+            })
+        })
+        .collect();
+    json!({
+        "ok": report.passed(),
+        "command": "dev",
+        "synced": report.synced.iter().map(|change| &change.path).collect::<Vec<_>>(),
+        "tests": { "passed": report.tests.passed, "detail": report.tests.detail },
+        "samples": samples,
+        "duration_secs": report.duration.as_secs_f64(),
+        "error": error.map(error_json),
+    })
+}
+
+fn print_dev_report(project: &Project, report: &DevReport) {
+    if report.synced.is_empty() {
+        println!("Generated project up to date");
+    } else {
+        let files: Vec<String> = report.synced.iter().map(|change| relative(project, &change.path)).collect();
+        println!("Synced {} file(s): {}", files.len(), files.join(", "));
+    }
+
+    if report.tests.passed {
+        println!("[PASS] tests: {}", report.tests.detail);
+    } else {
+        println!("[FAIL] tests\n{}", report.tests.detail);
+    }
+    for sample in &report.samples {
+        if sample.passed {
+            println!("[PASS] {}: {}", relative(project, &sample.sample), sample.detail);
+        } else {
+            println!("[FAIL] {}\n{}", relative(project, &sample.sample), sample.detail);
         }
-        _ => panic!("Unknown subcommand"),
     }
+
+    let result = if report.passed() { "ok" } else { "FAILED" };
+    println!("{} in {:.1}s", result, report.duration.as_secs_f64());
 }
 
 // Ceres
@@ -53,6 +562,40 @@ fn cli() -> Command {
     .about("Ceres is an SDK for creating data collectors and verifiers for any data type to be sold on the Mercury Protocol")
     .subcommand_required(true)
     .allow_external_subcommands(true)
+    .arg(
+        Arg::new("project-dir")
+        .short('C')
+        .long("project-dir")
+        .value_name("DIR")
+        .global(true)
+        .help("Run as if ceres was started in DIR instead of the working directory")
+    )
+    .arg(
+        Arg::new("verbose")
+        .short('v')
+        .long("verbose")
+        .action(ArgAction::Count)
+        .global(true)
+        .help("Log more details to stderr, -vv for everything")
+    )
+    .arg(
+        Arg::new("quiet")
+        .short('q')
+        .long("quiet")
+        .action(ArgAction::SetTrue)
+        .global(true)
+        .conflicts_with("verbose")
+        .help("Only log warnings and errors")
+    )
+    .arg(
+        Arg::new("output")
+        .long("output")
+        .value_name("FORMAT")
+        .value_parser(["text", "json"])
+        .default_value("text")
+        .global(true)
+        .help("Print the result as text, or as a single JSON object on stdout")
+    )
     .subcommand(
         Command::new("init")
         .about("Creates a new empty collector-verifier project folder")
@@ -76,23 +619,160 @@ fn cli() -> Command {
             .action(ArgAction::SetTrue)
             .help("Initialize a Python collector package")
         )
+        .arg(dry_run_arg())
     )
     .subcommand(
         Command::new("gen")
         .about("Takes the verifier code and outputs a complete Risc0 program that can be used with Mercury")
+        .arg(dry_run_arg())
+    )
+    .subcommand(
+        Command::new("dev")
+        .about("Re-syncs the generated project with the verifier code and runs its tests")
+        .arg(
+            Arg::new("watch")
+            .long("watch")
+            .action(ArgAction::SetTrue)
+            .help("Run again whenever verifier/src, verifier/Cargo.toml or the samples change")
+        )
+        .arg(
+            Arg::new("execute")
+            .long("execute")
+            .action(ArgAction::SetTrue)
+            .help("Also run every file in verifier/samples through the verifier in risc0 dev mode, without proving")
+        )
     )
     .subcommand(
         Command::new("build")
         .about("Creates an executable binary from the verifier code")
+        .arg(
+            Arg::new("check-reproducible")
+            .long("check-reproducible")
+            .action(ArgAction::SetTrue)
+            .help("Build the guest twice in clean target directories and compare the ELF hashes and image IDs")
+        )
+        .arg(
+            Arg::new("no-regen")
+            .long("no-regen")
+            .action(ArgAction::SetTrue)
+            .help("Fail if the generated project is out of date with the verifier source, instead of re-syncing it")
+        )
+        .arg(
+            Arg::new("target")
+            .long("target")
+            .value_name("TRIPLE")
+            .help("Build a statically linked verifier for another platform, e.g. x86_64-unknown-linux-musl or aarch64-unknown-linux-gnu")
+        )
+    )
+    .subcommand(
+        Command::new("package")
+        .about("Packages the built verifier into a versioned archive in dist/")
+    )
+    .subcommand(
+        Command::new("doctor")
+        .about("Checks that the tools needed to generate and build verifiers are installed")
+    )
+    .subcommand(
+        Command::new("status")
+        .about("Shows whether the generated project, the build and the PR are up to date, and the samples and receipts")
+    )
+    .subcommand(
+        Command::new("keygen")
+        .about("Creates the key pair the collector signs its data with")
+        .arg(
+            Arg::new("secp256k1")
+            .long("secp256k1")
+            .action(ArgAction::SetTrue)
+            .help("Create a secp256k1 key instead of an ed25519 key")
+        )
+    )
+    .subcommand(
+        Command::new("sign")
+        .about("Signs a data file with the collector key, for verification in the guest")
+        .arg(arg!(<FILE> "The data file to sign"))
+        .arg_required_else_help(true)
+        .arg(
+            Arg::new("key")
+            .long("key")
+            .value_name("KEY_FILE")
+            .help("The private key to sign with, collector.key in the project by default")
+        )
+        .arg(
+            Arg::new("out")
+            .short('o')
+            .long("out")
+            .value_name("OUT_FILE")
+            .help("Where to write the signed data, FILE.signed by default")
+        )
     )
     .subcommand(
         Command::new("new-pr")
         .about("Prepare a pull request to the Mercury data collectors repo")
+        .args(pr_field_args())
+        .arg(
+            Arg::new("from")
+            .long("from")
+            .value_name("FILE")
+            .help("Read answers from a TOML or JSON file whose keys are the field names, e.g. data_source")
+        )
+        .arg(
+            Arg::new("no-input")
+            .long("no-input")
+            .action(ArgAction::SetTrue)
+            .help("Fail if a field has no value instead of asking for it")
+        )
     )
     .subcommand(
         Command::new("add-pr")
         .about("Updates the Mercury data collectors repo with a new PR")
-        .arg(arg!(<PR_FILE> "Path to a pr.md file for a collector verifier project"))
+        .arg(arg!(<PR> "Path to the PR.md written by new-pr, or to the project folder it is in"))
         .arg_required_else_help(true)
     )
+    .subcommand(
+        Command::new("registry")
+        .about("Reads the collectors in a clone of the Mercury data collectors repo")
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("list")
+            .about("Lists the collectors with their current version and image ID")
+        )
+        .subcommand(
+            Command::new("search")
+            .about("Lists the collectors whose name, description, source repo, contact or image IDs contain QUERY")
+            .arg(arg!(<QUERY> "The text to look for, ignoring case"))
+            .arg_required_else_help(true)
+        )
+        .subcommand(
+            Command::new("show")
+            .about("Shows a collector and all its image IDs")
+            .arg(arg!(<COLLECTOR> "The ID, name or an image ID of the collector"))
+            .arg_required_else_help(true)
+        )
+    )
+}
+
+/// A flag for every PR field, e.g. --data-source for data_source.
+fn pr_field_args() -> Vec<Arg> {
+    [
+        ("name", "name", "The name of the collector-verifier, the project name by default"),
+        ("description", "description", "A short description of what it does, at most 280 characters"),
+        ("data_description", "data-description", "What data is collected: type, structure, file format, size"),
+        ("data_source", "data-source", "Where the data is collected from"),
+        ("data_usefulness", "data-usefulness", "Why collecting this data is useful"),
+        ("code_explanation", "code-explanation", "How the collector and verifier programs work"),
+        ("image_id", "image-id", "The Image ID of the verifier, from ceres.lock by default"),
+        ("source_code", "source-code", "Link to the GitHub repo with the source code, the git remote by default"),
+        ("email", "email", "Contact email, the git user.email by default"),
+    ]
+    .into_iter()
+    .map(|(id, long, help)| Arg::new(id).long(long).value_name("TEXT").help(help))
+    .collect()
+}
+
+fn dry_run_arg() -> Arg {
+    Arg::new("dry-run")
+    .long("dry-run")
+    .action(ArgAction::SetTrue)
+    .help("List the files that would be created or modified and show diffs of the manifests and main.rs, without writing anything")
 }
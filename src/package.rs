@@ -0,0 +1,275 @@
+/*
+Packages a built verifier into a versioned archive in dist/ that DAO node operators can install without building from source.
+The archive contains:
+- bin/<name>: the host binary
+- guest/<name>: the guest ELF (and the risc0 program binary if present)
+- IMAGE_ID: the image ID of the guest
+- manifest.json: project, toolchain and dependency versions, every locked package as name@version
+- SHA256SUMS: checksums of all the files above
+*/
+use flate2::{write::GzEncoder, Compression};
+use serde::Serialize;
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    build,
+    error::{CeresError, IoContext, Result},
+    lock,
+    project::Project,
+};
+
+#[derive(Serialize, Debug)]
+struct Manifest {
+    name: String,
+    version: String,
+    image_id: String,
+    elf_sha256: String,
+    ceres_version: String,
+    toolchain: lock::Toolchain,
+    /// name@version of every locked package. A crate can be locked at several versions.
+    dependencies: BTreeSet<String>,
+}
+
+#[derive(Debug)]
+pub struct PackageResult {
+    pub version: String,
+    /// dist/<name>-<version>.tar.gz
+    pub archive: PathBuf,
+    /// dist/<name>-<version>.tar.gz.sha256
+    pub archive_checksum: PathBuf,
+}
+
+pub fn package(project: &Project) -> Result<PackageResult> {
+    let project_dir = project.root();
+    let verifier_dir = project.verifier_dir();
+
+    let build_lock = lock::read(project_dir).ok_or_else(|| {
+        CeresError::environment(format!("No {} found", lock::LOCK_FILE))
+            .with_hint("Run \"ceres build\" before packaging the verifier")
+    })?;
+    if build_lock.source_hash != lock::source_hash(&verifier_dir) {
+        return Err(CeresError::environment("The verifier source has changed since the last build")
+            .with_hint("Run \"ceres build\" again before packaging"));
+    }
+
+    let name = build_lock.name.clone();
+    let version = get_package_version(&verifier_dir.join("Cargo.toml"))?;
+    let out_dir = verifier_dir.join("out").join(&name);
+
+    let host_path = build_lock.host_binary_path(project_dir);
+    if !host_path.exists() {
+        return Err(CeresError::environment(format!("Host binary not found at {}", host_path.display()))
+            .with_hint("Run \"ceres build\" first"));
+    }
+    let elf_path = build::find_built_guest_elf(&out_dir, &name, build_lock.target.as_deref())
+        .ok_or_else(|| {
+            CeresError::environment("Could not find the guest ELF in target/riscv-guest")
+                .with_hint("Run \"ceres build\" first")
+        })?;
+
+    let package_name = format!("{}-{}", name, version);
+    let dist_dir = project_dir.join("dist");
+    fs::create_dir_all(&dist_dir).context("Failed to create dist folder")?;
+    // the files are staged in a temporary folder, removed when it goes out of scope, so dist/ only gets the archive
+    let staging = tempfile::Builder::new()
+        .prefix("ceres-package-")
+        .tempdir()
+        .context("Failed to create a temporary package folder")?;
+    let staging_dir = staging.path().join(&package_name);
+    fs::create_dir_all(staging_dir.join("bin")).context("Failed to create package folder")?;
+    fs::create_dir_all(staging_dir.join("guest")).context("Failed to create package folder")?;
+
+    fs::copy(&host_path, staging_dir.join("bin").join(&name)).context("Failed to copy host binary")?;
+    fs::copy(&elf_path, staging_dir.join("guest").join(&name)).context("Failed to copy guest ELF")?;
+    let binary_path = elf_path.with_extension("bin");
+    if binary_path.exists() {
+        fs::copy(&binary_path, staging_dir.join("guest").join(format!("{}.bin", name)))
+            .context("Failed to copy guest program binary")?;
+    }
+    fs::write(staging_dir.join("IMAGE_ID"), format!("{}\n", build_lock.image_id))
+        .context("Failed to write IMAGE_ID")?;
+
+    let manifest = Manifest {
+        name: name.clone(),
+        version: version.clone(),
+        image_id: build_lock.image_id,
+        elf_sha256: build_lock.elf_sha256,
+        ceres_version: env!("CARGO_PKG_VERSION").to_string(),
+        toolchain: build_lock.toolchain,
+        dependencies: get_locked_dependencies(&out_dir.join("Cargo.lock")),
+    };
+    let serialized = serde_json::to_string_pretty(&manifest)
+        .map_err(|err| CeresError::io(format!("Failed to serialize manifest.json: {}", err)))?;
+    fs::write(staging_dir.join("manifest.json"), serialized).context("Failed to write manifest.json")?;
+
+    write_checksums(&staging_dir)?;
+
+    let archive_path = dist_dir.join(format!("{}.tar.gz", package_name));
+    let archive_file = fs::File::create(&archive_path).context("Failed to create archive")?;
+    let mut archive = tar::Builder::new(GzEncoder::new(archive_file, Compression::default()));
+    archive
+        .append_dir_all(&package_name, &staging_dir)
+        .context("Failed to add files to archive")?;
+    archive
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .context("Failed to write archive")?;
+
+    let archive_checksum = dist_dir.join(format!("{}.tar.gz.sha256", package_name));
+    fs::write(
+        &archive_checksum,
+        format!("{}  {}.tar.gz\n", lock::file_sha256(&archive_path)?, package_name),
+    )
+    .context("Failed to write archive checksum")?;
+
+    Ok(PackageResult {
+        version,
+        archive: archive_path,
+        archive_checksum,
+    })
+}
+
+/// Reads `version = "..."` from the [package] section of the verifier Cargo.toml.
+pub(crate) fn get_package_version(file_path: &Path) -> Result<String> {
+    let content = fs::read_to_string(file_path).context("Failed to read verifier Cargo.toml")?;
+    Ok(content
+        .lines()
+        .take_while(|line| !line.trim().starts_with("[dependencies]"))
+        .find_map(|line| line.trim().strip_prefix("version = "))
+        .map(|version| version.trim_matches('"').to_string())
+        .unwrap_or_else(|| String::from("0.0.0")))
+}
+
+/// Collects `name@version` of every package resolved in the generated project's Cargo.lock.
+fn get_locked_dependencies(file_path: &Path) -> BTreeSet<String> {
+    let lock: toml::Value = match fs::read_to_string(file_path).ok().and_then(|content| toml::from_str(&content).ok()) {
+        Some(lock) => lock,
+        None => return BTreeSet::new(),
+    };
+
+    let packages = lock.get("package").and_then(|packages| packages.as_array()).map_or(&[][..], |packages| packages);
+    packages
+        .iter()
+        .filter_map(|package| {
+            let name = package.get("name")?.as_str()?;
+            let version = package.get("version")?.as_str()?;
+            Some(format!("{}@{}", name, version))
+        })
+        .collect()
+}
+
+fn write_checksums(staging_dir: &Path) -> Result<()> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    for sub_dir in ["bin", "guest"] {
+        let entries = fs::read_dir(staging_dir.join(sub_dir)).context("Failed to list package files")?;
+        for entry in entries.flatten() {
+            files.push(entry.path());
+        }
+    }
+    files.push(staging_dir.join("IMAGE_ID"));
+    files.push(staging_dir.join("manifest.json"));
+    files.sort();
+
+    let mut checksums = String::new();
+    for file in &files {
+        let relative = file.strip_prefix(staging_dir).unwrap_or(file);
+        checksums.push_str(&format!("{}  {}\n", lock::file_sha256(file)?, relative.display()));
+    }
+    fs::write(staging_dir.join("SHA256SUMS"), checksums).context("Failed to write SHA256SUMS")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locked_dependencies_keep_every_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let lock_file = dir.path().join("Cargo.lock");
+        fs::write(
+            &lock_file,
+            r#"# This file is automatically @generated by Cargo.
+version = 3
+
+[[package]]
+name = "host"
+version = "0.1.0"
+dependencies = [
+ "syn 1.0.109",
+ "syn 2.0.48",
+]
+
+[[package]]
+name = "syn"
+version = "1.0.109"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "72b64191b275b66ffe2469e8af2c1cfe3bafa67529147aac4d2ea2f2ff36bb10"
+
+[[package]]
+name = "syn"
+version = "2.0.48"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#,
+        )
+        .unwrap();
+
+        let dependencies: Vec<String> = get_locked_dependencies(&lock_file).into_iter().collect();
+        assert_eq!(dependencies, ["host@0.1.0", "syn@1.0.109", "syn@2.0.48"]);
+        assert!(get_locked_dependencies(&dir.path().join("missing.lock")).is_empty());
+    }
+
+    #[test]
+    fn package_leaves_only_the_archive_in_dist() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("weather");
+        fs::create_dir_all(root.join("verifier")).unwrap();
+        fs::write(root.join("verifier").join("Cargo.toml"), "[package]\nname = \"verifier\"\nversion = \"0.2.0\"\n").unwrap();
+        let project = Project::open(&root).unwrap();
+
+        let target_dir = project.out_dir().join("target");
+        fs::create_dir_all(target_dir.join("riscv-guest").join("release")).unwrap();
+        fs::create_dir_all(target_dir.join("release")).unwrap();
+        fs::write(target_dir.join("riscv-guest").join("release").join("weather"), "elf").unwrap();
+        fs::write(target_dir.join("release").join("weather"), "host").unwrap();
+        let build_lock = lock::Lock {
+            name: String::from("weather"),
+            image_id: "ab".repeat(32),
+            source_hash: lock::source_hash(&project.verifier_dir()),
+            host_binary: PathBuf::from("verifier/out/weather/target/release/weather"),
+            ..lock::Lock::default()
+        };
+        lock::write(&root, &build_lock).unwrap();
+
+        let result = package(&project).unwrap();
+        let mut dist: Vec<String> = fs::read_dir(root.join("dist"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        dist.sort();
+        assert_eq!(dist, ["weather-0.2.0.tar.gz", "weather-0.2.0.tar.gz.sha256"]);
+
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(fs::File::open(result.archive).unwrap()));
+        let mut files: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .map(|entry| entry.unwrap())
+            .filter(|entry| entry.header().entry_type().is_file())
+            .map(|entry| entry.path().unwrap().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                "weather-0.2.0/IMAGE_ID",
+                "weather-0.2.0/SHA256SUMS",
+                "weather-0.2.0/bin/weather",
+                "weather-0.2.0/guest/weather",
+                "weather-0.2.0/manifest.json"
+            ]
+        );
+    }
+}
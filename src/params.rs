@@ -0,0 +1,247 @@
+/*
+Verifier parameters, declared in verifier/params.json, so thresholds and constants can change without changing the image ID:
+
+{
+    "min_records": { "type": "integer", "default": 10 },
+    "sensor_ids": { "type": "string-list" }
+}
+
+`ceres gen` generates a params module in the host and the guest. The host loads the defaults, overridden by the JSON
+object in the file given with --params=FILE, and passes them to the guest. guestlib reads them with
+`crate::params::get()`, and the guest commits them to the journal so every receipt shows the parameters it was verified with.
+*/
+use serde::Deserialize;
+use serde_json::Value;
+use std::{collections::BTreeMap, fs};
+
+use crate::{
+    error::{CeresError, IoContext, Result},
+    project::Project,
+};
+
+pub const PARAMS_FILE: &str = "params.json";
+
+/// Parameter names become field names in the generated Params struct.
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "static", "struct",
+    "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+];
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ParamType {
+    Integer,
+    Number,
+    String,
+    Boolean,
+    IntegerList,
+    NumberList,
+    StringList,
+}
+
+impl ParamType {
+    fn rust_type(self) -> &'static str {
+        match self {
+            ParamType::Integer => "i64",
+            ParamType::Number => "f64",
+            ParamType::String => "String",
+            ParamType::Boolean => "bool",
+            ParamType::IntegerList => "Vec<i64>",
+            ParamType::NumberList => "Vec<f64>",
+            ParamType::StringList => "Vec<String>",
+        }
+    }
+
+    fn accepts(self, value: &Value) -> bool {
+        match self {
+            ParamType::Integer => value.is_i64(),
+            ParamType::Number => value.is_number(),
+            ParamType::String => value.is_string(),
+            ParamType::Boolean => value.is_boolean(),
+            ParamType::IntegerList => value.as_array().is_some_and(|values| values.iter().all(Value::is_i64)),
+            ParamType::NumberList => value.as_array().is_some_and(|values| values.iter().all(Value::is_number)),
+            ParamType::StringList => value.as_array().is_some_and(|values| values.iter().all(Value::is_string)),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Param {
+    #[serde(rename = "type")]
+    pub param_type: ParamType,
+    /// Parameters without a default must be given with --params=FILE.
+    pub default: Option<Value>,
+}
+
+/// The parameters declared in verifier/params.json by name, if the project has any.
+pub fn read(project: &Project) -> Result<Option<BTreeMap<String, Param>>> {
+    let path = project.verifier_dir().join(PARAMS_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+    let params: BTreeMap<String, Param> = serde_json::from_str(&content).map_err(|err| {
+        CeresError::template(format!("{} is not valid: {}", path.display(), err))
+            .with_hint("See \"Verifier parameters\" in the ceres README")
+    })?;
+
+    for (name, param) in &params {
+        let is_identifier = name.chars().next().is_some_and(|c| c.is_ascii_lowercase() || c == '_')
+            && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !is_identifier || RUST_KEYWORDS.contains(&name.as_str()) {
+            return Err(CeresError::template(format!("Invalid {}: {} is not a valid parameter name", PARAMS_FILE, name))
+                .with_hint("Use lowercase letters, digits and underscores, and no Rust keywords"));
+        }
+        if param.default.as_ref().is_some_and(|default| !param.param_type.accepts(default)) {
+            return Err(CeresError::template(format!(
+                "Invalid {}: the default of {} is not of type {:?}",
+                PARAMS_FILE, name, param.param_type
+            )));
+        }
+    }
+    Ok(Some(params))
+}
+
+/// The params module of the guest: the Params struct, set by the generated main and read by guestlib.
+pub fn guest_source(params: &BTreeMap<String, Param>) -> String {
+    let mut source = params_struct(params);
+    source.push_str("static PARAMS: OnceLock<Params> = OnceLock::new();\n\n");
+    source.push_str("/// The parameters the data is verified with.\n");
+    source.push_str("pub fn get() -> &'static Params {\n");
+    source.push_str("    PARAMS.get().expect(\"the verifier parameters are read before guestlib::verify\")\n");
+    source.push_str("}\n\n");
+    source.push_str("pub fn set(params: Params) {\n");
+    source.push_str("    let _ = PARAMS.set(params);\n");
+    source.push_str("}\n");
+    source.replacen("use serde::", "use std::sync::OnceLock;\nuse serde::", 1)
+}
+
+/// The params module of the host: the Params struct and loading it from the defaults and --params=FILE.
+pub fn host_source(params: &BTreeMap<String, Param>) -> Result<String> {
+    let defaults: serde_json::Map<String, Value> = params
+        .iter()
+        .filter_map(|(name, param)| param.default.clone().map(|default| (name.clone(), default)))
+        .collect();
+    let defaults = serde_json::to_string(&defaults)
+        .map_err(|err| CeresError::template(format!("Failed to serialize the parameter defaults: {}", err)))?;
+
+    let mut source = params_struct(params);
+    source.push_str(&format!("const DEFAULTS: &str = {:?};\n\n", defaults));
+    source.push_str("impl Params {\n");
+    source.push_str("    /// The defaults from params.json, overridden by the JSON object in the file given with --params=FILE.\n");
+    source.push_str("    pub fn load(ctx: &Context) -> Params {\n");
+    source.push_str("        let mut values: serde_json::Map<String, serde_json::Value> = serde_json::from_str(DEFAULTS).unwrap();\n");
+    source.push_str("        if let Some(path) = ctx.option(\"params\") {\n");
+    source.push_str("            let content = std::fs::read_to_string(path).expect(\"Failed to read the parameters file\");\n");
+    source.push_str("            let overrides: serde_json::Map<String, serde_json::Value> = serde_json::from_str(&content).expect(\"The parameters file must be a JSON object\");\n");
+    source.push_str("            values.extend(overrides);\n");
+    source.push_str("        }\n");
+    source.push_str("        serde_json::from_value(serde_json::Value::Object(values)).expect(\"Invalid verifier parameters\")\n");
+    source.push_str("    }\n");
+    source.push_str("}\n");
+    Ok(source.replacen("use serde::", "use ceres_host::Context;\nuse serde::", 1))
+}
+
+fn params_struct(params: &BTreeMap<String, Param>) -> String {
+    let mut source = String::new();
+    source.push_str(&format!("// Generated by ceres from verifier/{}, edit that file instead\n", PARAMS_FILE));
+    source.push_str("use serde::{Deserialize, Serialize};\n\n");
+    source.push_str("#[derive(Serialize, Deserialize, Debug, Clone)]\n");
+    source.push_str("#[serde(deny_unknown_fields)]\n");
+    source.push_str("pub struct Params {\n");
+    for (name, param) in params {
+        source.push_str(&format!("    pub {}: {},\n", name, param.param_type.rust_type()));
+    }
+    source.push_str("}\n\n");
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_params(json: &str) -> Result<Option<BTreeMap<String, Param>>> {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("verifier")).unwrap();
+        fs::write(dir.path().join("verifier").join(PARAMS_FILE), json).unwrap();
+        read(&Project::open(dir.path()).unwrap())
+    }
+
+    const PARAMS: &str = r#"{
+        "min_records": { "type": "integer", "default": 10 },
+        "max_temperature": { "type": "number", "default": 45.5 },
+        "sensor_ids": { "type": "string-list" }
+    }"#;
+
+    #[test]
+    fn reads_the_declared_params() {
+        let params = read_params(PARAMS).unwrap().unwrap();
+        let names: Vec<&str> = params.keys().map(String::as_str).collect();
+        assert_eq!(names, ["max_temperature", "min_records", "sensor_ids"]);
+        assert_eq!(params["min_records"].param_type, ParamType::Integer);
+        assert_eq!(params["min_records"].default, Some(Value::from(10)));
+        assert_eq!(params["sensor_ids"].default, None);
+
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("verifier")).unwrap();
+        assert!(read(&Project::open(dir.path()).unwrap()).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_unknown_types_and_fields() {
+        let err = read_params(r#"{ "limit": { "type": "float" } }"#).unwrap_err();
+        assert!(err.message.contains("unknown variant `float`"), "{}", err.message);
+        assert!(read_params(r#"{ "limit": { "type": "integer", "min": 1 } }"#).is_err());
+        assert!(read_params(r#"{ "limit": { "default": 1 } }"#).is_err());
+    }
+
+    #[test]
+    fn rejects_defaults_of_another_type() {
+        for (param_type, default) in [
+            ("integer", "1.5"),
+            ("integer", "\"1\""),
+            ("number", "true"),
+            ("string", "1"),
+            ("boolean", "\"yes\""),
+            ("integer-list", "[1, 2.5]"),
+            ("number-list", "1"),
+            ("string-list", "[\"a\", 1]"),
+        ] {
+            let err = read_params(&format!(r#"{{ "limit": {{ "type": "{}", "default": {} }} }}"#, param_type, default))
+                .unwrap_err();
+            assert!(err.message.contains("the default of limit is not of type"), "{}", err.message);
+        }
+        assert!(read_params(r#"{ "limit": { "type": "number", "default": 3 } }"#).is_ok());
+        assert!(read_params(r#"{ "ids": { "type": "integer-list", "default": [] } }"#).is_ok());
+    }
+
+    #[test]
+    fn rejects_names_that_are_not_identifiers() {
+        for name in ["Limit", "2nd", "max-records", "type", ""] {
+            let err = read_params(&format!(r#"{{ "{}": {{ "type": "integer" }} }}"#, name)).unwrap_err();
+            assert!(err.message.contains("is not a valid parameter name"), "{}", err.message);
+        }
+    }
+
+    #[test]
+    fn generates_the_host_and_guest_modules() {
+        let params = read_params(PARAMS).unwrap().unwrap();
+        let fields = "pub struct Params {\n    pub max_temperature: f64,\n    pub min_records: i64,\n    pub sensor_ids: Vec<String>,\n}\n";
+
+        let guest = guest_source(&params);
+        assert!(guest.starts_with("// Generated by ceres from verifier/params.json, edit that file instead\nuse std::sync::OnceLock;\nuse serde::"));
+        assert!(guest.contains(fields));
+        assert!(guest.contains("pub fn get() -> &'static Params {"));
+        assert!(guest.contains("pub fn set(params: Params) {"));
+
+        let host = host_source(&params).unwrap();
+        assert!(host.contains("use ceres_host::Context;\nuse serde::"));
+        assert!(host.contains(fields));
+        // parameters without a default are not in DEFAULTS, they must be given with --params=FILE
+        assert!(host.contains(r#"const DEFAULTS: &str = "{\"max_temperature\":45.5,\"min_records\":10}";"#));
+        assert!(host.contains("pub fn load(ctx: &Context) -> Params {"));
+    }
+}
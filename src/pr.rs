@@ -1,9 +1,21 @@
-use std::{fs, fs::OpenOptions, io, io::BufWriter, io::Write};
+use std::{
+    env, fs,
+    io,
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    process::Command,
+};
 use serde::{Serialize, Deserialize};
-use serde_json;
 
-#[derive(Default, Serialize, Deserialize, Debug)]
-struct Pr {
+use crate::{
+    error::{CeresError, IoContext, Result},
+    image_id, lock, package,
+    project::Project,
+    schema::{self, Schema},
+};
+
+#[derive(Default, Serialize, Deserialize, Debug, Clone)]
+pub struct Pr {
     pub name: String,
     pub description: String,
     pub data_description: String,
@@ -13,145 +25,821 @@ struct Pr {
     pub image_id: String,
     pub email: String,
     pub source_code: String,
+    /// The version of the verifier crate, from verifier/Cargo.toml. Not asked for.
+    #[serde(default)]
+    pub version: String,
+}
+
+impl Pr {
+    /// A field by its key in FIELDS.
+    pub fn get(&self, key: &str) -> &str {
+        match key {
+            "name" => &self.name,
+            "description" => &self.description,
+            "data_description" => &self.data_description,
+            "data_source" => &self.data_source,
+            "data_usefulness" => &self.data_usefulness,
+            "code_explanation" => &self.code_explanation,
+            "image_id" => &self.image_id,
+            "email" => &self.email,
+            "source_code" => &self.source_code,
+            "version" => &self.version,
+            _ => "",
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: String) {
+        let field = match key {
+            "name" => &mut self.name,
+            "description" => &mut self.description,
+            "data_description" => &mut self.data_description,
+            "data_source" => &mut self.data_source,
+            "data_usefulness" => &mut self.data_usefulness,
+            "code_explanation" => &mut self.code_explanation,
+            "image_id" => &mut self.image_id,
+            "email" => &mut self.email,
+            "source_code" => &mut self.source_code,
+            "version" => &mut self.version,
+            _ => return,
+        };
+        *field = value;
+    }
+}
+
+/// PR field values given up front, from flags, a TOML or JSON file, or `[package.metadata.ceres.pr]` in the verifier Cargo.toml.
+/// The keys are the field names of Pr.
+#[derive(Default, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PrAnswers {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub data_description: Option<String>,
+    pub data_source: Option<String>,
+    pub data_usefulness: Option<String>,
+    pub code_explanation: Option<String>,
+    pub image_id: Option<String>,
+    pub email: Option<String>,
+    pub source_code: Option<String>,
+}
+
+impl PrAnswers {
+    /// Reads answers from a .toml file, or a JSON file for any other extension.
+    pub fn read(path: &Path) -> Result<PrAnswers> {
+        let content = fs::read_to_string(path).map_err(|err| {
+            CeresError::user_input(format!("Failed to read {}: {}", path.display(), err))
+        })?;
+        let answers = if path.extension().is_some_and(|extension| extension == "toml") {
+            toml::from_str(&content).map_err(|err| err.to_string())
+        } else {
+            serde_json::from_str(&content).map_err(|err| err.to_string())
+        };
+        answers.map_err(|err| {
+            CeresError::user_input(format!("{} is not a valid PR answers file: {}", path.display(), err))
+                .with_hint(format!("The keys are {}", FIELDS.iter().map(|(key, _)| *key).collect::<Vec<_>>().join(", ")))
+        })
+    }
+
+    /// The answer for a field, by its key in FIELDS.
+    pub fn get(&self, key: &str) -> Option<&String> {
+        match key {
+            "name" => self.name.as_ref(),
+            "description" => self.description.as_ref(),
+            "data_description" => self.data_description.as_ref(),
+            "data_source" => self.data_source.as_ref(),
+            "data_usefulness" => self.data_usefulness.as_ref(),
+            "code_explanation" => self.code_explanation.as_ref(),
+            "image_id" => self.image_id.as_ref(),
+            "email" => self.email.as_ref(),
+            "source_code" => self.source_code.as_ref(),
+            _ => None,
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: String) {
+        let field = match key {
+            "name" => &mut self.name,
+            "description" => &mut self.description,
+            "data_description" => &mut self.data_description,
+            "data_source" => &mut self.data_source,
+            "data_usefulness" => &mut self.data_usefulness,
+            "code_explanation" => &mut self.code_explanation,
+            "image_id" => &mut self.image_id,
+            "email" => &mut self.email,
+            "source_code" => &mut self.source_code,
+            _ => return,
+        };
+        *field = Some(value);
+    }
+
+    /// Fills the fields that have no answer here from `other`.
+    fn or(mut self, other: PrAnswers) -> PrAnswers {
+        for (key, _) in FIELDS {
+            if self.get(key).is_none() {
+                if let Some(value) = other.get(key) {
+                    self.set(key, value.clone());
+                }
+            }
+        }
+        self
+    }
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct PrOptions {
+    /// Values from command line flags, which take precedence over the file and the manifest.
+    pub answers: PrAnswers,
+    /// A TOML or JSON file of answers.
+    pub file: Option<PathBuf>,
+    /// Fail when a field has no value instead of prompting for it.
+    pub no_input: bool,
+}
+
+/// Every PR field, in the order they are asked: its key in flags and files, and the question.
+pub const FIELDS: &[(&str, &str)] = &[
+    ("name", "Please enter the name of your collector-verifier:"),
+    ("description", "Please add a short (max. 280 characters) description of what this collector-verifier does:"),
+    ("data_description", "Please explain what data will be collected (type, structure, file format, size, etc.):"),
+    ("data_source", "Please explain where this data will be collected from:"),
+    ("data_usefulness", "Please explain why collecting this data is useful and where can it be used:"),
+    ("code_explanation", "Please briefly explain how the collector and verifier programs work:"),
+    ("image_id", "Please enter the Image ID of the verifier program:"),
+    ("source_code", "Please enter the link to a GitHub repo with the source code:"),
+    ("email", "Please add an email where people can reach out about this collector-verifier:"),
+];
+
+/// The fields reviewers read most closely, answered in Markdown over several lines, and what to cover in them.
+const LONG_FORM: &[(&str, &str)] = &[
+    ("data_description", "- the data type and file format\n- its structure: the fields, their types and units\n- the typical and maximum size of a file"),
+    ("data_usefulness", "- who would buy this data\n- what it can be used for"),
+    ("code_explanation", "- how the collector gets the data and sends it\n- what prepare in hostlib.rs does to it\n- what verify in guestlib.rs checks"),
+];
+
+/// Ends a long-form answer typed on stdin.
+const END_OF_ANSWER: &str = ".";
+
+/// The table of the verifier Cargo.toml that holds PR answers. It is not part of the verifier source, see lock::source_hash.
+pub(crate) const MANIFEST_ANSWERS: [&str; 4] = ["package", "metadata", "ceres", "pr"];
+
+/// The PR document written by `new-pr` and read by `add-pr`, in the project root.
+pub const PR_FILE: &str = "PR.md";
+
+/// The fields in the front matter of PR.md, in order.
+const FRONT_MATTER: &[&str] = &["name", "version", "image_id", "email", "source_code"];
+
+/// The fields in the body of PR.md, under these headings.
+const SECTIONS: &[(&str, &str)] = &[
+    ("description", "Collector-verifier description"),
+    ("data_description", "Data to be collected: type, structure, file format, size, etc."),
+    ("data_source", "Data will be collected from"),
+    ("data_usefulness", "This data is worth collecting because"),
+    ("code_explanation", "Explanation of code"),
+];
+
+fn read_answer(stdin: &io::Stdin) -> Result<String> {
+    let mut answer = String::new();
+    let read = stdin.read_line(&mut answer).context("Failed to read input")?;
+    if read == 0 {
+        return Err(CeresError::user_input("The input ended before every PR field was answered")
+            .with_hint("Pass the answers as flags or with --from FILE, and --no-input to run without a terminal"));
+    }
+    Ok(answer.trim().to_string())
 }
 
-pub fn new_pr() {
+#[derive(Debug)]
+pub struct PrFiles {
+    pub pr_file: PathBuf,
+}
+
+/// Asks for every PR field on stdin. The Image ID is taken from ceres.lock when the project has been built.
+pub fn prompt(project: &Project) -> Result<Pr> {
+    gather(project, &PrOptions::default())
+}
+
+/// Collects the PR fields from the flags, then the answers file, then the verifier manifest, then ceres.lock for the
+/// Image ID. The fields still missing are asked on stdin, suggesting the project name and the git email and remote,
+/// or with `no_input`, taken from those suggestions when there are some and reported as missing otherwise.
+pub fn gather(project: &Project, options: &PrOptions) -> Result<Pr> {
+    let mut given = options.answers.clone();
+    if let Some(file) = &options.file {
+        given = given.or(PrAnswers::read(file)?);
+    }
+    given = given.or(manifest_answers(project)?);
+
+    // the Image ID is checked against the last build, which must be of the current source
+    let build_lock = lock::read(project.root());
+    if build_lock.as_ref().is_some_and(|build_lock| build_lock.source_hash != lock::source_hash(&project.verifier_dir())) {
+        return Err(CeresError::environment("The verifier source has changed since the last build")
+            .with_hint("Run \"ceres build\""));
+    }
+    if given.image_id.is_none() {
+        if let Some(build_lock) = &build_lock {
+            log::info!("Using Image ID {} from {}", build_lock.image_id, lock::LOCK_FILE);
+            given.image_id = Some(build_lock.image_id.clone());
+        }
+    }
+    let suggested = suggestions(project);
+    let facts = Facts {
+        built_image_id: build_lock.map(|build_lock| build_lock.image_id),
+        schema: schema::read(project)?,
+    };
+
     let stdin = io::stdin();
     let mut pr = Pr::default();
+    let mut missing = Vec::new();
+    for (key, question) in FIELDS {
+        let value = match (given.get(key), options.no_input) {
+            (Some(value), _) => check(key, value, &facts).map_err(|reason| invalid_answer(key, reason))?,
+            (None, true) => match suggested.get(key) {
+                Some(value) => check(key, value, &facts).map_err(|reason| invalid_answer(key, reason))?,
+                None => {
+                    missing.push(key.replace('_', "-"));
+                    continue;
+                }
+            },
+            (None, false) => ask(&stdin, key, question, suggested.get(key), &facts)?,
+        };
+        pr.set(key, value);
+    }
+
+    if !missing.is_empty() {
+        return Err(CeresError::user_input(format!("Missing PR fields: {}", missing.join(", "))).with_hint(format!(
+            "Pass them as flags (--{}), in a file given with --from, or in [package.metadata.ceres.pr] in verifier/Cargo.toml",
+            missing[0]
+        )));
+    }
+    pr.version = package::get_package_version(&project.verifier_dir().join("Cargo.toml"))?;
+    Ok(pr)
+}
+
+/// What the project says about some PR fields, to check the answers against.
+struct Facts {
+    /// The image ID recorded in ceres.lock by the last build.
+    built_image_id: Option<String>,
+    schema: Option<Schema>,
+}
+
+/// Asks until the answer is valid. An empty answer takes the suggestion.
+/// Prompts go to stderr, so stdout only carries the result, e.g. with `--output json`.
+fn ask(stdin: &io::Stdin, key: &str, question: &str, suggestion: Option<&String>, facts: &Facts) -> Result<String> {
+    let mut previous = String::new();
+    loop {
+        match suggestion {
+            Some(suggestion) => eprintln!("{} [{}]", question, suggestion),
+            None => eprintln!("{}", question),
+        }
+        let long_form = LONG_FORM.iter().find(|(long_key, _)| *long_key == key);
+        let mut answer = match long_form {
+            Some((_, guidance)) => read_long_answer(stdin, key, question, guidance, &previous)?,
+            None => read_answer(stdin)?,
+        };
+        if answer.is_empty() {
+            if let Some(suggestion) = suggestion {
+                answer = suggestion.clone();
+            }
+        }
+        match check(key, &answer, facts) {
+            Ok(value) => {
+                eprintln!("\n");
+                return Ok(value);
+            }
+            Err(reason) => {
+                eprintln!("{}. Please try again.\n", reason);
+                previous = answer;
+            }
+        }
+    }
+}
 
-    println!("Please enter the name of your collector-verifier:");
-    let mut name = String::new();
-    stdin.read_line(&mut name).expect("Failed to read input");
-    name = name.trim().to_string();
-    pr.name = name;
-    println!("\n");
-
-    let mut length: usize = 0;
-    while length == 0 || length > 280 {
-        let mut desc = String::new();
-        println!("Please add a short (max. 280 characters) description of what this collector-verifier does:");
-        stdin.read_line(&mut desc).expect("Failed to read input");
-        desc = desc.trim().to_string();
-        length = desc.chars().count();
-        pr.description = desc;
-    }
-    println!("\n");
-
-    println!(
-        "Please explain what data will be collected (type, structure, file format, size, etc.):"
+/// A multi-line Markdown answer: written in $VISUAL or $EDITOR when stdin is a terminal and one is set,
+/// otherwise typed on stdin up to a line with only END_OF_ANSWER.
+/// In the editor, the file starts with the previous answer when it was not valid.
+fn read_long_answer(stdin: &io::Stdin, key: &str, question: &str, guidance: &str, previous: &str) -> Result<String> {
+    let editor = env::var("VISUAL").or_else(|_| env::var("EDITOR")).ok().filter(|editor| !editor.trim().is_empty());
+    if let Some(editor) = editor.filter(|_| stdin.is_terminal()) {
+        return edit_answer(&editor, key, question, guidance, previous);
+    }
+
+    eprintln!("(Markdown, end with a line containing only \"{}\")", END_OF_ANSWER);
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        let read = stdin.read_line(&mut line).context("Failed to read input")?;
+        if read == 0 {
+            return Err(CeresError::user_input(format!("The input ended before the line with only \"{}\"", END_OF_ANSWER))
+                .with_hint("Pass the answers as flags or with --from FILE, and --no-input to run without a terminal"));
+        }
+        let line = line.trim_end_matches(['\n', '\r']);
+        if line.trim() == END_OF_ANSWER {
+            break;
+        }
+        lines.push(line.to_string());
+    }
+    Ok(lines.join("\n").trim().to_string())
+}
+
+/// Opens the editor on a file with the question and guidance in an HTML comment, and returns what was written below it.
+fn edit_answer(editor: &str, key: &str, question: &str, guidance: &str, previous: &str) -> Result<String> {
+    // created with a random name readable by the user only, and removed when it goes out of scope
+    let file = tempfile::Builder::new()
+        .prefix(&format!("ceres-pr-{}-", key))
+        .suffix(".md")
+        .tempfile()
+        .context("Failed to create a file for the editor")?;
+    let path = file.path();
+    let template = format!(
+        "<!--\n{}\n\n{}\n\nWrite your answer in Markdown below this comment, it is copied into PR.md as is.\nComments are removed. Save and close the editor when you are done.\n-->\n\n{}",
+        question, guidance, previous
     );
-    let mut data_desc = String::new();
-    stdin
-        .read_line(&mut data_desc)
-        .expect("Failed to read input");
-    data_desc = data_desc.trim().to_string();
-    pr.data_description = data_desc;
-    println!("\n");
-
-    println!("Please explain where this data will be collected from:");
-    let mut data_source = String::new();
-    stdin
-        .read_line(&mut data_source)
-        .expect("Failed to read input");
-    data_source = data_source.trim().to_string();
-    pr.data_source = data_source;
-    println!("\n");
-
-    println!("Please explain why collecting this data is useful ane where can it be used:");
-    let mut usefulness = String::new();
-    stdin
-        .read_line(&mut usefulness)
-        .expect("Failed to read input");
-    usefulness = usefulness.trim().to_string();
-    pr.data_usefulness = usefulness;
-    println!("\n");
-
-    println!("Please briefly explain how the collector and verifier programs work: ");
-    let mut code_expl = String::new();
-    stdin
-        .read_line(&mut code_expl)
-        .expect("Failed to read input");
-    code_expl = code_expl.trim().to_string();
-    pr.code_explanation = code_expl;
-    println!("\n");
-
-    println!("Please enter the Image ID of the verifier program:");
-    let mut img_id = String::new();
-    stdin.read_line(&mut img_id).expect("Failed to read input");
-    img_id = img_id.trim().to_string();
-    pr.image_id = img_id;
-    println!("\n");
-
-    println!("Please enter the link to a GitHub repo with the source code:");
-    let mut source_code = String::new();
-    stdin
-        .read_line(&mut source_code)
-        .expect("Failed to read input");
-    source_code = source_code.trim().to_string();
-    pr.source_code = source_code;
-    println!("\n");
-
-    println!("Please add an email where people can reach out about this collector-verifier:");
-    let mut email = String::new();
-    stdin.read_line(&mut email).expect("Failed to read input");
-    email = email.trim().to_string();
-    pr.email = email;
-    println!("\n");
-
-    let pr_file = fs::File::create("PR.md").unwrap();
-    let mut writer = BufWriter::new(pr_file);
-    writeln!(writer, "# {}   \n", pr.name).expect("failed to write line");
-    writeln!(writer, "Image ID: {}   \n", pr.image_id).expect("failed to write");
-    writeln!(writer, "Contact email: {}   \n", pr.email).expect("failed to write");
-
-    writeln!(writer, "## Collector-verifier description:   ").expect("failed to write");
-    writeln!(writer, "{}   \n", pr.description).expect("failed to write");
-
-    writeln!(
-        writer,
-        "## Data to be collected: type, structure, file format, size, etc.:   "
-    )
-    .expect("failed to write");
-    writeln!(writer, "{}    \n", pr.data_description).expect("failed to write");
-
-    writeln!(writer, "## Data will be collected from:    ").expect("failed to write");
-    writeln!(writer, "{}    \n", pr.data_source).expect("failed to write");
-
-    writeln!(writer, "## This data is worth collecting because:   ").expect("failed to write");
-    writeln!(writer, "{}    \n", pr.data_usefulness).expect("failed to write");
-
-    writeln!(writer, "## Explanation of code:    ").expect("failed to write");
-    writeln!(writer, "{}    \n", pr.code_explanation).expect("failed to write");
-
-    let serialized_pr = serde_json::to_string(&pr).unwrap();
-    let mut pr_info = fs::File::create(".prinfo").unwrap();
-    pr_info.write_all(serialized_pr.as_bytes()).unwrap();
-
-
-    println!("Success! PR.md file generated. Now please follow the following steps: ");
-    println!("1. Run \"git clone https://github.com/mercury-protocol/mcy-data-collectors.git\"");
-    println!("2. Go into the cloned repo and run \"ceres add-pr <PATH TO YOUR PR.MD file>\"");
-    println!("3. Push your changes and submit a pull request where you paste in the contents of the PR.md file");
-}
-
-pub fn add_pr(pr_file: &String) {
-    // open PR file and read the relevant lines
-    let pr_info = fs::read_to_string(format!("{}/.prinfo", pr_file)).unwrap();
-
-    let pr: Pr = serde_json::from_str(&pr_info).unwrap();
-
-    // create the string that will be written 
-    let pr_str = format!("--- \n\n# {} \n**ID**: {}\n**Image ID**: {}\n**Source code**: {}\n**Full PR text**: {}\n**Description**: {}", pr.name, " ",pr.image_id,pr.source_code," ", pr.description);
-
-    // open the data-collectors.md file
-    let mut file = OpenOptions::new()
-        .write(true)
-        .append(true)
-        .open("data-collectors.md")
-        .expect("Cant open data-collectors.md. Are you in the correct repo?");
-
-    // write the string to the file
-    file.write_all(pr_str.as_bytes()).expect("Failed to write to file");
-
-    println!("Data collector list updated");
-}
\ No newline at end of file
+    fs::write(path, template).context(format!("Failed to write {}", path.display()))?;
+
+    // the editor may come with arguments, e.g. "code --wait"
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+    let status = Command::new(program).args(words).arg(path).status();
+    let content = fs::read_to_string(path);
+
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => return Err(CeresError::user_input(format!("The editor {} exited with {}", editor, status))),
+        Err(err) => {
+            return Err(CeresError::environment(format!("Failed to run the editor {}: {}", editor, err))
+                .with_hint("Set VISUAL or EDITOR to an installed editor, or unset them to type the answer here"))
+        }
+    }
+    let content = content.context("Failed to read the answer from the editor")?;
+    Ok(strip_comments(&content).trim().to_string())
+}
+
+/// Removes the HTML comments of a Markdown text.
+fn strip_comments(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("<!--") {
+        stripped.push_str(&rest[..start]);
+        rest = match rest[start..].find("-->") {
+            Some(end) => &rest[start + end + 3..],
+            None => "",
+        };
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// The value stored for a field, or why the answer is not valid.
+fn check(key: &str, answer: &str, facts: &Facts) -> std::result::Result<String, String> {
+    match key {
+        "description" => {
+            let length = answer.chars().count();
+            if length == 0 || length > 280 {
+                return Err(format!("The description must be 1 to 280 characters long, not {}", length));
+            }
+            Ok(answer.to_string())
+        }
+        "image_id" => {
+            let normalized = image_id::normalize(answer).ok_or_else(|| {
+                String::from("The Image ID must be 64 hex characters, or 0x followed by 64 hex characters")
+            })?;
+            match &facts.built_image_id {
+                Some(built) if *built != normalized => Err(format!(
+                    "{} is not the Image ID of the current build, {} in {}",
+                    normalized, built, lock::LOCK_FILE
+                )),
+                _ => Ok(normalized),
+            }
+        }
+        "email" => check_email(answer).map(|_| answer.to_string()),
+        "source_code" => check_repo_url(answer),
+        "data_description" => match &facts.schema {
+            Some(schema) => check_against_schema(answer, schema).map(|_| answer.to_string()),
+            None => Ok(answer.to_string()),
+        },
+        _ => Ok(answer.to_string()),
+    }
+}
+
+fn check_email(email: &str) -> std::result::Result<(), String> {
+    let invalid = || Err(format!("{:?} is not a valid email address, e.g. name@example.com", email));
+    let (local, domain) = match email.split_once('@') {
+        Some(parts) => parts,
+        None => return invalid(),
+    };
+    let valid_local = !local.is_empty() && !local.starts_with('.') && !local.ends_with('.');
+    let labels: Vec<&str> = domain.split('.').collect();
+    let valid_domain = labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    if !valid_local || !valid_domain || email.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return invalid();
+    }
+    Ok(())
+}
+
+/// An https link to a repository: a host and at least an owner and a repository name. Returns it without a trailing / or .git.
+fn check_repo_url(url: &str) -> std::result::Result<String, String> {
+    let invalid = || Err(format!("{:?} is not a repository URL, e.g. https://github.com/owner/repo", url));
+    let rest = match url.strip_prefix("https://") {
+        Some(rest) => rest,
+        None => return invalid(),
+    };
+    let mut parts = rest.trim_end_matches('/').split('/');
+    let host = parts.next().unwrap_or_default();
+    let path: Vec<&str> = parts.collect();
+    let valid_host = host.contains('.') && host.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-' || c == ':');
+    if !valid_host || path.len() < 2 || path.iter().any(|segment| segment.is_empty()) || url.chars().any(char::is_whitespace) {
+        return invalid();
+    }
+
+    let trimmed = url.trim_end_matches('/');
+    Ok(trimmed.strip_suffix(".git").unwrap_or(trimmed).to_string())
+}
+
+/// Whether `word` appears in `text` as a whole word, so a field named id is not found in "valid".
+fn mentions(text: &str, word: &str) -> bool {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(word).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + word.len()..].chars().next();
+        !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+    })
+}
+
+/// The data description must name the format and every field declared in verifier/schema.json.
+fn check_against_schema(description: &str, schema: &Schema) -> std::result::Result<(), String> {
+    let lowercase = description.to_lowercase();
+    let format = match schema.format {
+        schema::Format::Csv => "csv",
+        schema::Format::Json | schema::Format::JsonArray => "json",
+    };
+    let mut missing: Vec<&str> = schema
+        .fields
+        .iter()
+        .map(|field| field.name.as_str())
+        .filter(|name| !mentions(&lowercase, &name.to_lowercase()))
+        .collect();
+    if !mentions(&lowercase, format) {
+        missing.insert(0, format);
+    }
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "The data description does not mention {}, declared in verifier/{}",
+            missing.join(", "),
+            schema::SCHEMA_FILE
+        ))
+    }
+}
+
+fn invalid_answer(key: &str, reason: String) -> CeresError {
+    CeresError::user_input(format!("Invalid {}: {}", key.replace('_', "-"), reason))
+}
+
+/// The answers in `[package.metadata.ceres.pr]` of the verifier Cargo.toml.
+fn manifest_answers(project: &Project) -> Result<PrAnswers> {
+    let manifest_path = project.verifier_dir().join("Cargo.toml");
+    let content = fs::read_to_string(&manifest_path).context(format!("Failed to read {}", manifest_path.display()))?;
+    let manifest: toml::Value = toml::from_str(&content).map_err(|err| {
+        CeresError::template(format!("{} is not valid TOML: {}", manifest_path.display(), err))
+    })?;
+
+    let table = MANIFEST_ANSWERS.iter().try_fold(&manifest, |value, key| value.get(key)).cloned();
+    match table {
+        Some(table) => table.try_into().map_err(|err| {
+            CeresError::template(format!("[package.metadata.ceres.pr] in {} is not valid: {}", manifest_path.display(), err))
+        }),
+        None => Ok(PrAnswers::default()),
+    }
+}
+
+/// Suggested answers from the project: its name, and the email and remote of its git repo.
+fn suggestions(project: &Project) -> PrAnswers {
+    let root = project.root().to_string_lossy().to_string();
+    let git = |args: &[&str]| {
+        let mut git_args = vec!["-C", root.as_str()];
+        git_args.extend_from_slice(args);
+        lock::command_output("git", &git_args).filter(|output| !output.is_empty())
+    };
+
+    PrAnswers {
+        name: Some(project.name().to_string()),
+        email: git(&["config", "user.email"]),
+        source_code: git(&["remote", "get-url", "origin"]).map(|remote| {
+            let remote = remote.strip_suffix(".git").unwrap_or(&remote);
+            match remote.strip_prefix("git@github.com:") {
+                Some(path) => format!("https://github.com/{}", path),
+                None => remote.to_string(),
+            }
+        }),
+        ..Default::default()
+    }
+}
+
+/// Writes PR.md into the project root.
+pub fn prepare_pr(project: &Project, pr: &Pr) -> Result<PrFiles> {
+    let pr_file = project.root().join(PR_FILE);
+    fs::write(&pr_file, render(pr)).context(format!("Failed to write {}", PR_FILE))?;
+    Ok(PrFiles { pr_file })
+}
+
+/// PR.md: the short fields in a YAML front matter, and the Markdown answers under a heading each.
+/// The values in the front matter are written as double-quoted strings, which are both YAML and JSON.
+pub fn render(pr: &Pr) -> String {
+    let mut text = String::from("---\n");
+    for key in FRONT_MATTER {
+        text.push_str(&format!("{}: {}\n", key, serde_json::Value::from(pr.get(key))));
+    }
+    // the blank line keeps the closing --- from turning the front matter into a heading when PR.md is pasted into a PR
+    text.push_str("\n---\n\n");
+
+    text.push_str(&format!("# {}\n", pr.name));
+    for (key, title) in SECTIONS {
+        text.push_str(&format!("\n## {}\n\n", title));
+        for line in pr.get(key).lines() {
+            text.push_str(line);
+            // an answer line that reads like one of the headings gets a closing " ##", which Markdown doesn't show
+            if known_heading(line).is_some() {
+                text.push_str(" ##");
+            }
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// The index in SECTIONS of a "## <title>" line, and the number of " ##" closing sequences after the title.
+fn known_heading(line: &str) -> Option<(usize, usize)> {
+    let mut title = line.strip_prefix("## ")?.trim_end();
+    let mut closings = 0;
+    while let Some(rest) = title.strip_suffix(" ##") {
+        title = rest.trim_end();
+        closings += 1;
+    }
+    let title = title.trim().trim_end_matches(':').trim();
+    let index = SECTIONS.iter().position(|(_, known)| *known == title)?;
+    Some((index, closings))
+}
+
+/// Parses a PR.md written by `render`. Values in the front matter may also be plain or single-quoted YAML scalars.
+pub fn parse(text: &str) -> std::result::Result<Pr, String> {
+    let mut lines = text.trim_start_matches('\u{feff}').lines();
+    if lines.next().map(str::trim) != Some("---") {
+        return Err(String::from("it does not start with a --- front matter"));
+    }
+
+    let mut pr = Pr::default();
+    let mut found: Vec<&str> = Vec::new();
+    loop {
+        let line = lines.next().ok_or("the front matter is not closed with ---")?;
+        let line = line.trim();
+        if line == "---" {
+            break;
+        }
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = line.split_once(':').ok_or_else(|| format!("{:?} is not a key: value line", line))?;
+        let key = FRONT_MATTER
+            .iter()
+            .find(|field| **field == key.trim())
+            .ok_or_else(|| format!("{} is not a front matter field, they are {}", key.trim(), FRONT_MATTER.join(", ")))?;
+        let value = value.trim();
+        let value = if value.starts_with('"') {
+            serde_json::from_str::<String>(value).map_err(|err| format!("the value of {} is not a valid string: {}", key, err))?
+        } else if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+            value[1..value.len() - 1].replace("''", "'")
+        } else {
+            value.to_string()
+        };
+        pr.set(key, value);
+        found.push(key);
+    }
+
+    // the text under each heading of SECTIONS, which are only recognized in order. Other headings are part of the answers
+    let mut sections: Vec<Vec<&str>> = Vec::new();
+    for line in lines {
+        let line = match known_heading(line) {
+            Some((index, 0)) if index == sections.len() => {
+                sections.push(Vec::new());
+                continue;
+            }
+            Some((_, closings)) if closings > 0 => line.strip_suffix(" ##").unwrap_or(line),
+            _ => line,
+        };
+        if let Some(section) = sections.last_mut() {
+            section.push(line);
+        }
+    }
+    for ((key, _), section) in SECTIONS.iter().zip(&sections) {
+        pr.set(key, section.join("\n").trim().to_string());
+        found.push(key);
+    }
+
+    let missing: Vec<&str> = FRONT_MATTER
+        .iter()
+        .chain(SECTIONS.iter().map(|(key, _)| key))
+        // PR.md files written before the version was recorded have none
+        .filter(|key| !found.contains(key) && **key != "version")
+        .copied()
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("it has no {}", missing.join(", ")));
+    }
+    Ok(pr)
+}
+
+/// Reads a PR.md, or the PR.md in a project folder.
+pub fn read(path: &Path) -> Result<Pr> {
+    let pr_file = if path.is_dir() { path.join(PR_FILE) } else { path.to_path_buf() };
+    let text = fs::read_to_string(&pr_file).map_err(|err| {
+        CeresError::user_input(format!("Failed to read {}: {}", pr_file.display(), err))
+            .with_hint("Pass the PR.md written by \"ceres new-pr\", or the project folder it is in")
+    })?;
+    parse(&text).map_err(|reason| {
+        CeresError::template(format!("{} is not a valid PR document: {}", pr_file.display(), reason))
+            .with_hint("Run \"ceres new-pr\" again to regenerate it")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_pr() -> Pr {
+        Pr {
+            name: String::from("Weather \"station\": v2"),
+            description: String::from("Collects temperature readings.\n\nSigned by the station key."),
+            data_description: String::from(
+                "A JSON array of readings.\n\n## Fields\n- `timestamp`: seconds\n- `value`: degrees\n\n## Explanation of code\n## Data will be collected from ##\nstill the data description",
+            ),
+            data_source: String::from("Weather stations run by volunteers"),
+            data_usefulness: String::from("Local climate models need *dense* measurements"),
+            code_explanation: String::from("```rust\nlet x = 1;\n```\n---\nThe guest checks every field."),
+            image_id: "ab".repeat(32),
+            email: String::from("collector@example.com"),
+            source_code: String::from("https://github.com/owner/weather"),
+            version: String::from("0.2.0"),
+        }
+    }
+
+    fn assert_same(left: &Pr, right: &Pr) {
+        for (key, _) in FIELDS.iter().chain(&[("version", "")]) {
+            assert_eq!(left.get(key), right.get(key), "field {}", key);
+        }
+    }
+
+    #[test]
+    fn render_then_parse_round_trips() {
+        let pr = sample_pr();
+        assert_same(&parse(&render(&pr)).unwrap(), &pr);
+    }
+
+    #[test]
+    fn answers_with_section_headings_are_kept_whole() {
+        let pr = sample_pr();
+        let text = render(&pr);
+        assert!(text.contains("\n## Explanation of code ##\n"));
+        let parsed = parse(&text).unwrap();
+        assert_eq!(parsed.data_description, pr.data_description);
+        assert_eq!(parsed.code_explanation, pr.code_explanation);
+    }
+
+    #[test]
+    fn headings_out_of_order_are_part_of_the_answer() {
+        let mut text = render(&Pr { data_description: String::from("short"), ..sample_pr() });
+        text = text.replace("\nshort\n", "\nshort\n## Explanation of code\nmore\n");
+        assert_eq!(parse(&text).unwrap().data_description, "short\n## Explanation of code\nmore");
+    }
+
+    #[test]
+    fn front_matter_accepts_plain_and_single_quoted_values() {
+        let text = render(&sample_pr())
+            .replace("name: \"Weather \\\"station\\\": v2\"", "name: 'it''s: v2'")
+            .replace("email: \"collector@example.com\"", "email: collector@example.com");
+        let pr = parse(&text).unwrap();
+        assert_eq!(pr.name, "it's: v2");
+        assert_eq!(pr.email, "collector@example.com");
+    }
+
+    #[test]
+    fn version_is_optional() {
+        let text = render(&sample_pr()).replace("version: \"0.2.0\"\n", "");
+        assert_eq!(parse(&text).unwrap().version, "");
+    }
+
+    #[test]
+    fn invalid_documents_are_rejected() {
+        assert!(parse("# name\n\n## Explanation of code\n").is_err());
+        assert!(parse("---\nname: x\n").is_err());
+        let unknown = render(&sample_pr()).replacen("email:", "mail:", 1);
+        assert!(parse(&unknown).unwrap_err().contains("mail"));
+        let missing = render(&sample_pr()).replace("## Explanation of code\n", "");
+        assert!(parse(&missing).unwrap_err().contains("code_explanation"));
+    }
+
+    #[test]
+    fn read_takes_a_file_or_a_project_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let pr = sample_pr();
+        fs::write(dir.path().join(PR_FILE), render(&pr)).unwrap();
+
+        assert_same(&read(&dir.path().join(PR_FILE)).unwrap(), &pr);
+        assert_same(&read(dir.path()).unwrap(), &pr);
+    }
+
+    #[test]
+    fn read_reports_a_missing_pr_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = read(dir.path()).unwrap_err();
+        assert_eq!(err.kind, crate::ErrorKind::UserInput);
+    }
+
+    #[test]
+    fn gather_takes_answers_from_the_verifier_manifest() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("verifier")).unwrap();
+        fs::write(
+            dir.path().join("verifier").join("Cargo.toml"),
+            format!(
+                r#"[package]
+name = "weather"
+version = "0.2.0"
+
+[package.metadata.ceres.pr]
+name = "Weather"
+description = "Temperature readings"
+data_description = "A JSON array of readings"
+data_source = "Weather stations"
+data_usefulness = "Climate models"
+code_explanation = """
+The guest checks
+every reading.
+"""
+image_id = "{}"
+email = "collector@example.com"
+source_code = "https://github.com/owner/weather"
+"#,
+                "ab".repeat(32)
+            ),
+        )
+        .unwrap();
+        let project = Project::open(dir.path()).unwrap();
+
+        let options = PrOptions {
+            answers: PrAnswers { email: Some(String::from("flag@example.com")), ..PrAnswers::default() },
+            no_input: true,
+            ..PrOptions::default()
+        };
+        let pr = gather(&project, &options).unwrap();
+        assert_eq!(pr.name, "Weather");
+        assert_eq!(pr.code_explanation, "The guest checks\nevery reading.\n");
+        assert_eq!(pr.email, "flag@example.com");
+        assert_eq!(pr.image_id, "ab".repeat(32));
+        assert_eq!(pr.version, "0.2.0");
+    }
+
+    #[test]
+    fn gather_rejects_a_stale_build() {
+        let dir = tempfile::tempdir().unwrap();
+        let verifier = dir.path().join("verifier");
+        fs::create_dir(&verifier).unwrap();
+        fs::write(verifier.join("Cargo.toml"), "[package]\nname = \"weather\"\nversion = \"0.2.0\"\n").unwrap();
+        let project = Project::open(dir.path()).unwrap();
+        let answers = PrAnswers {
+            name: Some(String::from("Weather")),
+            description: Some(String::from("Temperature readings")),
+            data_description: Some(String::from("A JSON array of readings")),
+            data_source: Some(String::from("Weather stations")),
+            data_usefulness: Some(String::from("Climate models")),
+            code_explanation: Some(String::from("The guest checks every reading")),
+            email: Some(String::from("collector@example.com")),
+            source_code: Some(String::from("https://github.com/owner/weather")),
+            ..PrAnswers::default()
+        };
+        let options = PrOptions { answers, no_input: true, ..PrOptions::default() };
+        let build = |source_hash: String| lock::Lock {
+            name: String::from("weather"),
+            image_id: "cd".repeat(32),
+            source_hash,
+            ..lock::Lock::default()
+        };
+
+        lock::write(dir.path(), &build(lock::source_hash(&verifier))).unwrap();
+        assert_eq!(gather(&project, &options).unwrap().image_id, "cd".repeat(32));
+        let other_image = PrOptions {
+            answers: PrAnswers { image_id: Some("ab".repeat(32)), ..options.answers.clone() },
+            ..options.clone()
+        };
+        assert!(gather(&project, &other_image).unwrap_err().message.contains("is not the Image ID of the current build"));
+
+        fs::write(verifier.join("Cargo.toml"), "[package]\nname = \"weather\"\nversion = \"0.3.0\"\n").unwrap();
+        let err = gather(&project, &options).unwrap_err();
+        assert_eq!(err.message, "The verifier source has changed since the last build");
+        assert_eq!(err.hint.as_deref(), Some("Run \"ceres build\""));
+    }
+}
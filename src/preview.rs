@@ -0,0 +1,93 @@
+/*
+Dry runs of gen and init. The planned files are compared with what is on disk, and a unified diff is shown for the
+generated manifests and main.rs files, which are the files ceres rewrites.
+*/
+use similar::TextDiff;
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{IoContext, Result},
+    lock,
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Create,
+    Modify,
+    Unchanged,
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            ChangeKind::Create => "create",
+            ChangeKind::Modify => "modify",
+            ChangeKind::Unchanged => "unchanged",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct FileChange {
+    /// Where the file would be written.
+    pub path: PathBuf,
+    pub kind: ChangeKind,
+    /// Unified diff against the file on disk, for manifests and main.rs files.
+    pub diff: Option<String>,
+}
+
+/// Compares the planned content of `path` with the file on disk. Diff headers are relative to `root`.
+pub fn compare(root: &Path, path: &Path, planned: &[u8]) -> FileChange {
+    let existing = fs::read(path).ok();
+    let kind = match &existing {
+        None => ChangeKind::Create,
+        Some(existing) if existing.as_slice() == planned => ChangeKind::Unchanged,
+        Some(_) => ChangeKind::Modify,
+    };
+
+    let diff = if kind != ChangeKind::Unchanged && shows_diff(path) {
+        let old = existing.as_deref().map(String::from_utf8_lossy).unwrap_or_default();
+        let new = String::from_utf8_lossy(planned);
+        let name = path.strip_prefix(root).unwrap_or(path).display().to_string();
+        let old_header = if existing.is_some() { format!("a/{}", name) } else { String::from("/dev/null") };
+        Some(
+            TextDiff::from_lines(old.as_ref(), new.as_ref())
+                .unified_diff()
+                .header(&old_header, &format!("b/{}", name))
+                .to_string(),
+        )
+    } else {
+        None
+    };
+
+    FileChange { path: path.to_path_buf(), kind, diff }
+}
+
+/// Compares every file generated in `staged` with the same relative path under `target`.
+pub fn compare_dirs(root: &Path, staged: &Path, target: &Path) -> Result<Vec<FileChange>> {
+    let mut files = Vec::new();
+    lock::collect_files(staged, &mut files);
+    files.sort();
+
+    let mut changes = Vec::new();
+    for file in files {
+        let relative = file.strip_prefix(staged).unwrap_or(&file);
+        if relative.components().any(|component| component.as_os_str() == ".git" || component.as_os_str() == "target") {
+            continue;
+        }
+        let planned = fs::read(&file).context(format!("Failed to read {}", file.display()))?;
+        changes.push(compare(root, &target.join(relative), &planned));
+    }
+    Ok(changes)
+}
+
+fn shows_diff(path: &Path) -> bool {
+    matches!(
+        path.file_name().and_then(|name| name.to_str()),
+        Some("Cargo.toml") | Some("main.rs")
+    )
+}
+
@@ -0,0 +1,128 @@
+/*
+A Project is a collector-verifier folder created by `ceres init`. It is named after its root folder, and every
+operation ceres offers on it is a method returning a structured result, so other tools can drive ceres programmatically.
+*/
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    build, dev, gen, init,
+    error::{CeresError, IoContext, Result},
+    package, pr, preview, signing, status,
+};
+
+#[derive(Debug, Clone)]
+pub struct Project {
+    root: PathBuf,
+    name: String,
+}
+
+impl Project {
+    /// Opens an existing project. The root must contain the verifier folder created by `ceres init`.
+    pub fn open(root: impl AsRef<Path>) -> Result<Project> {
+        let root = absolute(root.as_ref())?;
+        if !root.join("verifier").is_dir() {
+            return Err(CeresError::environment(format!(
+                "{} does not seem to be a Ceres project directory",
+                root.display()
+            ))
+            .with_hint("Run ceres from the folder created by \"ceres init\""));
+        }
+        Project::at(root)
+    }
+
+    /// Creates a new project folder with a collector in the given language and an empty verifier.
+    pub async fn init(root: impl AsRef<Path>, lang: init::CollectorLang) -> Result<Project> {
+        let project = Project::at(absolute(root.as_ref())?)?;
+        init::init(&project, lang).await?;
+        Ok(project)
+    }
+
+    /// Lists the files `init` would create at `root`, without touching anything.
+    pub fn preview_init(root: impl AsRef<Path>, lang: init::CollectorLang) -> Result<Vec<preview::FileChange>> {
+        let project = Project::at(absolute(root.as_ref())?)?;
+        Ok(init::preview(&project, lang))
+    }
+
+    fn at(root: PathBuf) -> Result<Project> {
+        let name = root
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .ok_or_else(|| CeresError::user_input(format!("{} is not a valid project folder", root.display())))?;
+        Ok(Project { root, name })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn verifier_dir(&self) -> PathBuf {
+        self.root.join("verifier")
+    }
+
+    /// The RiscZero project generated by `gen`.
+    pub fn out_dir(&self) -> PathBuf {
+        self.verifier_dir().join("out").join(&self.name)
+    }
+
+    pub fn gen(&self) -> Result<gen::GenResult> {
+        gen::gen(self)
+    }
+
+    /// Lists the files `gen` would create or modify, with diffs of the manifests and main.rs files.
+    pub fn preview_gen(&self) -> Result<Vec<preview::FileChange>> {
+        gen::preview(self)
+    }
+
+    /// Re-syncs the generated project, runs its native tests and, if asked, executes the samples in dev mode.
+    pub fn dev(&self, options: &dev::DevOptions) -> Result<dev::DevReport> {
+        dev::run(self, options)
+    }
+
+    /// Runs `dev` again every time the verifier code, manifest or samples change, until `on_cycle` returns false.
+    pub fn watch(&self, options: &dev::DevOptions, on_cycle: impl FnMut(&[PathBuf], Result<dev::DevReport>) -> bool) {
+        dev::watch(self, options, on_cycle)
+    }
+
+    /// Builds the verifier, re-syncing the generated project first if the verifier source changed since `gen`.
+    pub fn build(&self, options: &build::BuildOptions) -> Result<build::BuildResult> {
+        build::build(self, options)
+    }
+
+    pub fn check_reproducible(&self, options: &build::BuildOptions) -> Result<build::ReproducibilityReport> {
+        build::check_reproducible(self, options)
+    }
+
+    pub fn package(&self) -> Result<package::PackageResult> {
+        package::package(self)
+    }
+
+    /// Whether the generated project, the build and the PR are up to date, and which samples and receipts exist.
+    pub fn status(&self) -> status::ProjectStatus {
+        status::status(self)
+    }
+
+    /// Creates the collector key pair: collector.key in the project root and verifier/collector.pub for `gen`.
+    pub fn keygen(&self, scheme: signing::Scheme) -> Result<signing::KeyFiles> {
+        signing::keygen(self, scheme)
+    }
+
+    /// Writes PR.md, which `add_to_registry` reads, into the project root.
+    pub fn prepare_pr(&self, pr: &pr::Pr) -> Result<pr::PrFiles> {
+        pr::prepare_pr(self, pr)
+    }
+}
+
+fn absolute(path: &Path) -> Result<PathBuf> {
+    if path.is_absolute() {
+        Ok(path.to_path_buf())
+    } else {
+        Ok(env::current_dir().context("Failed to read the working directory")?.join(path))
+    }
+}
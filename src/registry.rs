@@ -0,0 +1,565 @@
+/*
+The registry of collector-verifiers in a clone of the mcy-data-collectors repo, updated by `ceres add-pr` and read by `ceres registry`:
+- data-collectors.json is the index: every collector with its ID, image IDs by version, source repo, contact and description
+- data-collectors.md is generated from the index, sorted by ID, each entry linking to the full PR text
+- the full PR text of every collector is kept in prs/<name>.md
+- adding a collector that is already listed updates it and keeps its ID, a new one gets the next ID
+
+Registries that only have a data-collectors.md written by older versions of ceres are imported into the index the first time.
+Image IDs are stored in the canonical form, the `0x` word form of older versions is converted when it is read.
+*/
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{CeresError, IoContext, Result},
+    image_id,
+    pr::{self, Pr},
+};
+
+/// The index of collectors, in the root of the registry repo.
+pub const INDEX_FILE: &str = "data-collectors.json";
+/// The Markdown list of collectors generated from the index, in the root of the registry repo.
+pub const LIST_FILE: &str = "data-collectors.md";
+/// The full PR texts, in the root of the registry repo.
+pub const PRS_DIR: &str = "prs";
+
+const SEPARATOR: &str = "---";
+const DEFAULT_HEADER: &str = "# Data collectors";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Index {
+    pub collectors: Vec<Collector>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collector {
+    pub id: u64,
+    pub name: String,
+    pub description: String,
+    pub source_code: String,
+    /// The contact email of the collector.
+    pub contact: String,
+    /// Oldest first, the last one is the current image ID.
+    pub image_ids: Vec<ImageVersion>,
+    /// The full PR text, relative to the registry root.
+    pub pr_text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageVersion {
+    /// The verifier version, unknown for collectors imported from an older data-collectors.md.
+    pub version: Option<String>,
+    pub image_id: String,
+}
+
+/// An entry of a data-collectors.md written by an older version of ceres.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub id: u64,
+    pub name: String,
+    pub image_id: String,
+    pub source_code: String,
+    /// A Markdown link to the full PR text.
+    pub pr_text: String,
+    pub description: String,
+}
+
+#[derive(Debug)]
+pub struct RegistryUpdate {
+    pub index_file: PathBuf,
+    pub list_file: PathBuf,
+    pub pr_text_file: PathBuf,
+    pub id: u64,
+    /// The collector was already listed and its entry was replaced.
+    pub updated: bool,
+}
+
+impl Collector {
+    /// The current image ID.
+    pub fn image_id(&self) -> Option<&ImageVersion> {
+        self.image_ids.last()
+    }
+
+    /// Whether the query appears in the name, description, source repo, contact or one of the image IDs, ignoring case.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        [&self.name, &self.description, &self.source_code, &self.contact]
+            .into_iter()
+            .chain(self.image_ids.iter().map(|image| &image.image_id))
+            .any(|field| field.to_lowercase().contains(&query))
+    }
+}
+
+impl Index {
+    /// The collectors matching `query`, see `Collector::matches`.
+    pub fn search(&self, query: &str) -> Vec<&Collector> {
+        self.collectors.iter().filter(|collector| collector.matches(query)).collect()
+    }
+
+    /// The collector with this ID, name or image ID, in either image ID form.
+    pub fn find(&self, key: &str) -> Option<&Collector> {
+        let key = key.trim();
+        let key_slug = slug(key);
+        let key_image_id = image_id::normalize(key);
+        self.collectors.iter().find(|collector| {
+            key.parse() == Ok(collector.id)
+                || slug(&collector.name) == key_slug
+                || collector.image_ids.iter().any(|image| Some(&image.image_id) == key_image_id.as_ref())
+        })
+    }
+}
+
+/// Reads data-collectors.json in `registry_dir`, or imports the entries of data-collectors.md when there is no index yet.
+pub fn read_index(registry_dir: &Path) -> Result<Index> {
+    let index_file = registry_dir.join(INDEX_FILE);
+    if index_file.exists() {
+        let content = fs::read_to_string(&index_file).context(format!("Failed to read {}", INDEX_FILE))?;
+        return serde_json::from_str(&content).map_err(|err| {
+            CeresError::user_input(format!("{} is not a valid registry index: {}", INDEX_FILE, err))
+                .with_hint(format!("Fix it, or delete it to import the collectors from {} again", LIST_FILE))
+        });
+    }
+
+    let text = fs::read_to_string(registry_dir.join(LIST_FILE)).map_err(|err| {
+        CeresError::environment(format!("Can't open {} or {}: {}", INDEX_FILE, LIST_FILE, err))
+            .with_hint("Run this command from the root of the mcy-data-collectors repo")
+    })?;
+    let (_, entries) = parse(&text);
+    log::info!("No {} yet, importing {} collectors from {}", INDEX_FILE, entries.len(), LIST_FILE);
+    let mut index = Index::default();
+    for entry in entries {
+        // the entries of older versions link to nothing, or to the PR text with [path](path)
+        let pr_text = entry.pr_text.rsplit_once("](").map(|(_, link)| link.trim_end_matches(')').to_string());
+        let image_id = image_id::normalize(&entry.image_id).ok_or_else(|| {
+            CeresError::user_input(format!(
+                "\"{}\" is not a valid Image ID, in collector {} of {}",
+                entry.image_id, entry.id, LIST_FILE
+            ))
+            .with_hint("Fix the Image ID in data-collectors.md, it is imported into the index")
+        })?;
+        index.collectors.push(Collector {
+            id: entry.id,
+            name: entry.name,
+            description: entry.description,
+            source_code: entry.source_code,
+            contact: String::new(),
+            image_ids: vec![ImageVersion { version: None, image_id }],
+            pr_text: pr_text.unwrap_or_default(),
+        });
+    }
+    index.collectors.sort_by_key(|collector| collector.id);
+    Ok(index)
+}
+
+/// Adds the collector-verifier described in `pr_path`, a PR.md or the project folder it is in, to the registry in `registry_dir`.
+pub fn add_to_registry(pr_path: &Path, registry_dir: &Path) -> Result<RegistryUpdate> {
+    let pr = pr::read(pr_path)?;
+    let pr_image_id = image_id::normalize(&pr.image_id).ok_or_else(|| {
+        CeresError::user_input(format!("\"{}\" in PR.md is not a valid Image ID", pr.image_id))
+            .with_hint("Run \"ceres new-pr\" again to take the Image ID of the current build")
+    })?;
+    let mut index = read_index(registry_dir)?;
+
+    let slug = slug(&pr.name);
+    if slug.is_empty() {
+        return Err(CeresError::user_input(format!("\"{}\" can't be used as a collector name", pr.name))
+            .with_hint("Use a name with letters or digits in PR.md"));
+    }
+    let pr_text = format!("{}/{}.md", PRS_DIR, slug);
+    let pr_text_file = registry_dir.join(&pr_text);
+    fs::create_dir_all(registry_dir.join(PRS_DIR)).context(format!("Failed to create {}", PRS_DIR))?;
+    fs::write(&pr_text_file, pr::render(&pr)).context(format!("Failed to write {}", pr_text_file.display()))?;
+
+    // older versions appended the same collector again every time, those entries are merged into one
+    let (existing, mut collectors): (Vec<Collector>, Vec<Collector>) =
+        index.collectors.into_iter().partition(|collector| self::slug(&collector.name) == slug);
+    let updated = !existing.is_empty();
+    let id = match existing.iter().map(|collector| collector.id).min() {
+        Some(id) => id,
+        None => collectors.iter().map(|collector| collector.id).max().unwrap_or(0) + 1,
+    };
+    let mut image_ids: Vec<ImageVersion> = Vec::new();
+    for image in existing.into_iter().flat_map(|collector| collector.image_ids) {
+        add_image_version(&mut image_ids, image);
+    }
+    let version = Some(pr.version.clone()).filter(|version| !version.is_empty());
+    add_image_version(&mut image_ids, ImageVersion { version, image_id: pr_image_id });
+
+    collectors.push(collector(id, &pr, image_ids, pr_text));
+    collectors.sort_by_key(|collector| collector.id);
+    index.collectors = collectors;
+
+    let index_file = registry_dir.join(INDEX_FILE);
+    let json = serde_json::to_string_pretty(&index)
+        .map_err(|err| CeresError::io(format!("Failed to serialize {}: {}", INDEX_FILE, err)))?;
+    fs::write(&index_file, json + "\n").context(format!("Failed to write {}", INDEX_FILE))?;
+
+    let list_file = registry_dir.join(LIST_FILE);
+    let header = fs::read_to_string(&list_file).map(|text| parse(&text).0).unwrap_or_default();
+    fs::write(&list_file, render(&header, &index)).context(format!("Failed to write {}", LIST_FILE))?;
+
+    Ok(RegistryUpdate {
+        index_file,
+        list_file,
+        pr_text_file,
+        id,
+        updated,
+    })
+}
+
+/// Records an image ID, replacing the one of the same version. An image ID that is already the current one is not added again.
+fn add_image_version(image_ids: &mut Vec<ImageVersion>, image: ImageVersion) {
+    if image_ids.last().is_some_and(|last| last.image_id == image.image_id && (image.version.is_none() || last.version == image.version)) {
+        return;
+    }
+    if image.version.is_some() {
+        image_ids.retain(|existing| existing.version != image.version);
+    }
+    image_ids.push(image);
+}
+
+fn collector(id: u64, pr: &Pr, image_ids: Vec<ImageVersion>, pr_text: String) -> Collector {
+    Collector {
+        id,
+        name: pr.name.clone(),
+        description: pr.description.clone(),
+        source_code: pr.source_code.clone(),
+        contact: pr.email.clone(),
+        image_ids,
+        pr_text,
+    }
+}
+
+/// The lowercase name with every run of other characters than letters and digits replaced by a dash.
+pub fn slug(name: &str) -> String {
+    let mut slug = String::new();
+    for character in name.trim().chars() {
+        if character.is_alphanumeric() {
+            slug.extend(character.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+/// Splits data-collectors.md into the text before the first entry and the entries, to import a registry without an index.
+/// Entries written by older versions of ceres have no ID and no trailing newline, they get the next free IDs in the order they appear.
+pub fn parse(text: &str) -> (String, Vec<Entry>) {
+    let mut chunks: Vec<Vec<&str>> = vec![Vec::new()];
+    for line in text.lines() {
+        // older versions ran the last line of the description into the "--- " separator of the next entry
+        let joined = line
+            .strip_suffix("--- ")
+            .or_else(|| line.trim_end().strip_suffix(SEPARATOR).filter(|rest| rest.starts_with("**Description**:")));
+        let (line, separator) = match joined {
+            Some(rest) if !rest.trim().is_empty() => (rest, true),
+            _ => (line, line.trim() == SEPARATOR),
+        };
+        if separator {
+            if line.trim() != SEPARATOR {
+                chunks.last_mut().expect("never empty").push(line);
+            }
+            chunks.push(Vec::new());
+        } else {
+            chunks.last_mut().expect("never empty").push(line);
+        }
+    }
+
+    let header = chunks.remove(0).join("\n");
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut without_id: Vec<Entry> = Vec::new();
+    for chunk in chunks.iter().filter(|chunk| chunk.iter().any(|line| !line.trim().is_empty())) {
+        let (id, entry) = parse_entry(chunk);
+        match id {
+            Some(id) => entries.push(Entry { id, ..entry }),
+            None => without_id.push(entry),
+        }
+    }
+    let next = entries.iter().map(|entry| entry.id).max().unwrap_or(0) + 1;
+    for (id, entry) in (next..).zip(without_id) {
+        entries.push(Entry { id, ..entry });
+    }
+    (header, entries)
+}
+
+fn parse_entry(lines: &[&str]) -> (Option<u64>, Entry) {
+    let mut entry = Entry {
+        id: 0,
+        name: String::new(),
+        image_id: String::new(),
+        source_code: String::new(),
+        pr_text: String::new(),
+        description: String::new(),
+    };
+    let mut id = None;
+    let mut description: Vec<&str> = Vec::new();
+    let mut in_description = false;
+    for line in lines {
+        let field = line.strip_prefix("**").and_then(|line| line.split_once("**:"));
+        match field {
+            Some((key, value)) if key != "Description" || !in_description => {
+                in_description = false;
+                let value = value.trim();
+                match key {
+                    "ID" => id = value.parse().ok(),
+                    "Image ID" => entry.image_id = image_id::normalize(value).unwrap_or_else(|| value.to_string()),
+                    "Source code" => entry.source_code = value.to_string(),
+                    "Full PR text" => entry.pr_text = value.to_string(),
+                    "Description" => {
+                        in_description = true;
+                        description.push(value);
+                    }
+                    _ => {}
+                }
+            }
+            _ if in_description => description.push(line),
+            _ => {
+                if let Some(name) = line.strip_prefix("# ").filter(|_| entry.name.is_empty()) {
+                    entry.name = name.trim().to_string();
+                }
+            }
+        }
+    }
+    entry.description = description.join("\n").trim().to_string();
+    (id, entry)
+}
+
+fn link(path: &str) -> String {
+    if path.is_empty() { String::new() } else { format!("[{}]({})", path, path) }
+}
+
+/// data-collectors.md for the index, with the collectors in the order of the index and a newline at the end.
+/// `header` is the text before the first entry of the current data-collectors.md.
+pub fn render(header: &str, index: &Index) -> String {
+    let notice = format!("<!-- Generated by ceres add-pr from {}, edit the index instead -->", INDEX_FILE);
+    let header: Vec<&str> = header.lines().filter(|line| line.trim() != notice).collect();
+    let mut text = match header.join("\n").trim_end() {
+        "" => DEFAULT_HEADER.to_string(),
+        header => header.to_string(),
+    };
+    text.push_str(&format!("\n\n{}\n", notice));
+    for collector in &index.collectors {
+        let (version, image_id) = match collector.image_id() {
+            Some(image) => (image.version.as_deref().unwrap_or("unknown"), image.image_id.as_str()),
+            None => ("unknown", ""),
+        };
+        text.push_str(&format!(
+            "\n{}\n\n# {}\n**ID**: {}\n**Version**: {}\n**Image ID**: {}\n**Source code**: {}\n**Contact**: {}\n**Full PR text**: {}\n**Description**: {}\n",
+            SEPARATOR,
+            collector.name,
+            collector.id,
+            version,
+            image_id,
+            collector.source_code,
+            collector.contact,
+            link(&collector.pr_text),
+            collector.description
+        ));
+        let earlier: Vec<String> = collector.image_ids[..collector.image_ids.len().saturating_sub(1)]
+            .iter()
+            .rev()
+            .map(|image| format!("{} ({})", image.image_id, image.version.as_deref().unwrap_or("unknown")))
+            .collect();
+        if !earlier.is_empty() {
+            text.push_str(&format!("**Earlier image IDs**: {}\n", earlier.join(", ")));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    /// A real 32 byte image ID in the canonical form, different for every seed.
+    fn image(seed: &str) -> String {
+        crate::lock::to_hex(&Sha256::digest(seed.as_bytes()))
+    }
+
+    /// The `0x`-prefixed word form printed by older versions of ceres.
+    fn legacy_form(image_id: &str) -> String {
+        let bytes: Vec<u8> = (0..32).map(|i| u8::from_str_radix(&image_id[i * 2..i * 2 + 2], 16).unwrap()).collect();
+        let words: Vec<String> = bytes
+            .chunks(4)
+            .map(|word| format!("{:08X}", u32::from_le_bytes(word.try_into().unwrap())))
+            .collect();
+        format!("0x{}", words.concat())
+    }
+
+    /// A data-collectors.md appended to by older versions of ceres: no IDs, no links, no newline after the description,
+    /// image IDs in the word form, and a collector that was added twice. The last entry was written by a version that
+    /// numbered the entries.
+    fn legacy_list() -> String {
+        format!(
+            "# Mercury data collectors\n\nThe collector-verifiers accepted so far.\n\
+--- \n\n# Weather \n**ID**:  \n**Image ID**: {}\n**Source code**: https://github.com/owner/weather\n**Full PR text**:  \n\
+**Description**: Temperature readings\nfrom volunteer stations--- \n\n\
+# Steps \n**ID**:  \n**Image ID**: {}\n**Source code**: https://github.com/owner/steps\n**Full PR text**:  \n**Description**: Daily step counts--- \n\n\
+# Weather \n**ID**:  \n**Image ID**: {}\n**Source code**: https://github.com/owner/weather\n**Full PR text**:  \n**Description**: Temperature readings, hourly\n\
+---\n\n# Air quality\n**ID**: 7\n**Image ID**: {}\n**Source code**: https://github.com/owner/air\n**Full PR text**: [prs/air-quality.md](prs/air-quality.md)\n**Description**: PM2.5 levels\n",
+            legacy_form(&image("weather 1")),
+            legacy_form(&image("steps")),
+            legacy_form(&image("weather 2")),
+            image("air").to_uppercase()
+        )
+    }
+
+    fn legacy_registry() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(LIST_FILE), legacy_list()).unwrap();
+        dir
+    }
+
+    fn write_pr(dir: &Path, name: &str, version: &str, image_id: &str) -> PathBuf {
+        let pr = Pr {
+            name: name.to_string(),
+            description: format!("{} data", name),
+            image_id: image_id.to_string(),
+            email: String::from("collector@example.com"),
+            source_code: format!("https://github.com/owner/{}", slug(name)),
+            version: version.to_string(),
+            ..Pr::default()
+        };
+        let path = dir.join(pr::PR_FILE);
+        fs::write(&path, pr::render(&pr)).unwrap();
+        path
+    }
+
+    fn history(collector: &Collector) -> Vec<(Option<&str>, &str)> {
+        collector.image_ids.iter().map(|image| (image.version.as_deref(), image.image_id.as_str())).collect()
+    }
+
+    #[test]
+    fn legacy_form_is_the_word_form() {
+        assert_eq!(image_id::normalize(&legacy_form(&image("steps"))), Some(image("steps")));
+        assert_ne!(legacy_form(&image("steps"))[2..].to_lowercase(), image("steps"));
+    }
+
+    #[test]
+    fn parse_splits_joined_legacy_entries() {
+        let (header, entries) = parse(&legacy_list());
+        assert_eq!(header, "# Mercury data collectors\n\nThe collector-verifiers accepted so far.");
+
+        let summary: Vec<(u64, &str, &str)> =
+            entries.iter().map(|entry| (entry.id, entry.name.as_str(), entry.image_id.as_str())).collect();
+        assert_eq!(
+            summary,
+            [
+                (7, "Air quality", image("air").as_str()),
+                (8, "Weather", image("weather 1").as_str()),
+                (9, "Steps", image("steps").as_str()),
+                (10, "Weather", image("weather 2").as_str())
+            ]
+        );
+        assert_eq!(entries[1].description, "Temperature readings\nfrom volunteer stations");
+        assert_eq!(entries[2].description, "Daily step counts");
+        assert_eq!(entries[0].pr_text, "[prs/air-quality.md](prs/air-quality.md)");
+    }
+
+    #[test]
+    fn read_index_imports_the_legacy_list() {
+        let registry = legacy_registry();
+        let index = read_index(registry.path()).unwrap();
+
+        let ids: Vec<(u64, &str)> = index.collectors.iter().map(|collector| (collector.id, collector.name.as_str())).collect();
+        assert_eq!(ids, [(7, "Air quality"), (8, "Weather"), (9, "Steps"), (10, "Weather")]);
+        assert_eq!(index.collectors[0].pr_text, "prs/air-quality.md");
+        assert_eq!(index.collectors[1].pr_text, "");
+        assert_eq!(history(&index.collectors[2]), [(None, image("steps").as_str())]);
+        assert!(!registry.path().join(INDEX_FILE).exists());
+    }
+
+    #[test]
+    fn read_index_rejects_invalid_legacy_image_ids() {
+        let registry = tempfile::tempdir().unwrap();
+        fs::write(registry.path().join(LIST_FILE), legacy_list().replace(&image("air").to_uppercase(), "0x44")).unwrap();
+        let err = read_index(registry.path()).unwrap_err();
+        assert!(err.message.contains("\"0x44\" is not a valid Image ID"), "{}", err.message);
+    }
+
+    #[test]
+    fn add_to_registry_keeps_the_id_of_a_listed_collector() {
+        let registry = legacy_registry();
+        let project = tempfile::tempdir().unwrap();
+
+        let update = add_to_registry(&write_pr(project.path(), "weather", "0.3.0", &image("weather 3")), registry.path()).unwrap();
+        assert!(update.updated);
+        assert_eq!(update.id, 8);
+
+        let index = read_index(registry.path()).unwrap();
+        let ids: Vec<(u64, &str)> = index.collectors.iter().map(|collector| (collector.id, collector.name.as_str())).collect();
+        assert_eq!(ids, [(7, "Air quality"), (8, "weather"), (9, "Steps")]);
+        assert_eq!(
+            history(&index.collectors[1]),
+            [(None, image("weather 1").as_str()), (None, image("weather 2").as_str()), (Some("0.3.0"), image("weather 3").as_str())]
+        );
+        assert_eq!(index.collectors[1].pr_text, "prs/weather.md");
+        assert!(registry.path().join("prs").join("weather.md").exists());
+
+        let (header, entries) = parse(&fs::read_to_string(registry.path().join(LIST_FILE)).unwrap());
+        assert!(header.starts_with("# Mercury data collectors\n\nThe collector-verifiers accepted so far."));
+        let listed: Vec<(u64, &str)> = entries.iter().map(|entry| (entry.id, entry.image_id.as_str())).collect();
+        assert_eq!(listed, [(7, image("air").as_str()), (8, image("weather 3").as_str()), (9, image("steps").as_str())]);
+
+        let update = add_to_registry(&write_pr(project.path(), "Wind", "0.1.0", &image("wind 1")), registry.path()).unwrap();
+        assert!(!update.updated);
+        assert_eq!(update.id, 10);
+        let update = add_to_registry(&write_pr(project.path(), "Wind", "0.1.1", &image("wind 2")), registry.path()).unwrap();
+        assert!(update.updated);
+        assert_eq!(update.id, 10);
+    }
+
+    #[test]
+    fn add_to_registry_records_image_ids_by_version() {
+        let registry = legacy_registry();
+        let project = tempfile::tempdir().unwrap();
+        let add = |version: &str, image_id: &str| {
+            add_to_registry(&write_pr(project.path(), "Steps", version, image_id), registry.path()).unwrap();
+            read_index(registry.path()).unwrap().find("steps").unwrap().clone()
+        };
+        let (legacy, a1, a2, b1) = (image("steps"), image("steps a1"), image("steps a2"), image("steps b1"));
+
+        assert_eq!(history(&add("1.0.0", &a1)), [(None, legacy.as_str()), (Some("1.0.0"), a1.as_str())]);
+        // the same build again changes nothing, also when the PR has the Image ID in the word form
+        assert_eq!(history(&add("1.0.0", &legacy_form(&a1))), [(None, legacy.as_str()), (Some("1.0.0"), a1.as_str())]);
+        // a rebuild of the same version replaces its image ID
+        assert_eq!(history(&add("1.0.0", &a2)), [(None, legacy.as_str()), (Some("1.0.0"), a2.as_str())]);
+        let steps = add("1.1.0", &b1);
+        assert_eq!(history(&steps), [(None, legacy.as_str()), (Some("1.0.0"), a2.as_str()), (Some("1.1.0"), b1.as_str())]);
+        assert_eq!(steps.image_id().unwrap().image_id, b1);
+
+        let list = fs::read_to_string(registry.path().join(LIST_FILE)).unwrap();
+        assert!(list.contains(&format!("**Version**: 1.1.0\n**Image ID**: {}\n", b1)));
+        assert!(list.contains(&format!("**Earlier image IDs**: {} (1.0.0), {} (unknown)\n", a2, legacy)));
+    }
+
+    #[test]
+    fn add_to_registry_rejects_an_invalid_image_id() {
+        let registry = legacy_registry();
+        let project = tempfile::tempdir().unwrap();
+        let err = add_to_registry(&write_pr(project.path(), "Steps", "1.0.0", "0xb1"), registry.path()).unwrap_err();
+        assert!(err.message.contains("\"0xb1\" in PR.md is not a valid Image ID"), "{}", err.message);
+        assert!(!registry.path().join(INDEX_FILE).exists());
+    }
+
+    #[test]
+    fn find_and_search() {
+        let registry = legacy_registry();
+        let index = read_index(registry.path()).unwrap();
+        assert_eq!(index.find("9").unwrap().name, "Steps");
+        assert_eq!(index.find("air quality").unwrap().id, 7);
+        assert_eq!(index.find(&image("air").to_uppercase()).unwrap().id, 7);
+        assert_eq!(index.find(&legacy_form(&image("steps"))).unwrap().id, 9);
+        assert!(index.find("rain").is_none());
+        assert!(index.find(&image("rain")).is_none());
+        assert_eq!(index.search("TEMPERATURE").len(), 2);
+        assert_eq!(slug("  Air  Quality (v2)! "), "air-quality-v2");
+    }
+}
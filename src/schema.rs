@@ -0,0 +1,356 @@
+/*
+A declarative data schema in verifier/schema.json. When it exists, `ceres gen` generates the guest's guestlib.rs from it
+instead of copying verifier/src/guestlib.rs, and the guest commits the schema hash to the journal.
+
+{
+    "format": "json-array",
+    "max_size": 1048576,
+    "fields": [
+        { "name": "timestamp", "type": "integer", "required": true, "min": 1600000000, "monotonic": true },
+        { "name": "id", "type": "string", "max_length": 64, "unique": true }
+    ]
+}
+
+See ceres_guest::schema for what every option checks.
+*/
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+
+use crate::{
+    error::{CeresError, IoContext, Result},
+    lock,
+    project::Project,
+};
+
+pub const SCHEMA_FILE: &str = "schema.json";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    Json,
+    JsonArray,
+    Csv,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+    #[serde(default)]
+    pub required: bool,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    #[serde(default)]
+    pub unique: bool,
+    #[serde(default)]
+    pub monotonic: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Schema {
+    pub format: Format,
+    #[serde(default)]
+    pub min_size: usize,
+    pub max_size: Option<usize>,
+    pub fields: Vec<Field>,
+}
+
+/// The schema in verifier/schema.json, if the project has one.
+pub fn read(project: &Project) -> Result<Option<Schema>> {
+    let path = project.verifier_dir().join(SCHEMA_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&path).context(format!("Failed to read {}", path.display()))?;
+    let schema: Schema = serde_json::from_str(&content).map_err(|err| {
+        CeresError::template(format!("{} is not a valid schema: {}", path.display(), err))
+    })?;
+    check(&schema)?;
+    Ok(Some(schema))
+}
+
+/// SHA-256 of the schema, independent of the formatting of schema.json.
+pub fn hash(schema: &Schema) -> Result<String> {
+    let serialized = serde_json::to_vec(schema)
+        .map_err(|err| CeresError::template(format!("Failed to serialize the schema: {}", err)))?;
+    Ok(lock::to_hex(&Sha256::digest(serialized)))
+}
+
+/// Source of a guestlib.rs whose `verify` checks the data against the schema, panicking with the reason when it doesn't match.
+pub fn guestlib_source(schema: &Schema) -> Result<String> {
+    let mut source = String::new();
+    source.push_str(&format!("// Generated by ceres from verifier/{}, edit that file instead\n", SCHEMA_FILE));
+    source.push_str("use ceres_guest::schema::{self, Field, FieldType, Format, Schema};\n\n");
+    source.push_str(&format!("pub const SCHEMA_HASH: &str = \"{}\";\n\n", hash(schema)?));
+
+    source.push_str("const FIELDS: &[Field] = &[\n");
+    for field in &schema.fields {
+        source.push_str(&format!(
+            "    Field {{ name: {:?}, field_type: FieldType::{:?}, required: {}, min: {:?}, max: {:?}, min_length: {:?}, max_length: {:?}, unique: {}, monotonic: {} }},\n",
+            field.name, field.field_type, field.required, field.min, field.max, field.min_length, field.max_length, field.unique, field.monotonic
+        ));
+    }
+    source.push_str("];\n\n");
+
+    source.push_str(&format!(
+        "const SCHEMA: Schema = Schema {{ format: Format::{:?}, min_size: {}, max_size: {}, fields: FIELDS }};\n\n",
+        schema.format,
+        schema.min_size,
+        schema.max_size.map_or(String::from("usize::MAX"), |max| max.to_string())
+    ));
+
+    source.push_str("pub fn verify(data: &Vec<u8>) -> bool {\n");
+    source.push_str("    if let Err(err) = schema::validate(data, &SCHEMA) {\n");
+    source.push_str("        panic!(\"the data does not match the schema: {}\", err);\n");
+    source.push_str("    }\n");
+    source.push_str("    true\n");
+    source.push_str("}\n");
+    Ok(source)
+}
+
+/// The largest size and length the guest can compare against: usize is 32 bits wide on riscv32.
+const GUEST_USIZE_MAX: usize = u32::MAX as usize;
+
+fn check(schema: &Schema) -> Result<()> {
+    let invalid = |message: String| {
+        Err(CeresError::template(format!("Invalid {}: {}", SCHEMA_FILE, message))
+            .with_hint("See \"Data schemas\" in the ceres README"))
+    };
+
+    if schema.fields.is_empty() {
+        return invalid(String::from("the schema has no fields"));
+    }
+    if schema.max_size.is_some_and(|max| max < schema.min_size) {
+        return invalid(String::from("max_size is less than min_size"));
+    }
+    if schema.min_size > GUEST_USIZE_MAX || schema.max_size.is_some_and(|max| max > GUEST_USIZE_MAX) {
+        return invalid(format!("min_size and max_size can't be more than {} bytes", GUEST_USIZE_MAX));
+    }
+    for (index, field) in schema.fields.iter().enumerate() {
+        if field.name.is_empty() {
+            return invalid(format!("field {} has no name", index));
+        }
+        if schema.fields[..index].iter().any(|other| other.name == field.name) {
+            return invalid(format!("field {} is declared twice", field.name));
+        }
+        let numeric = matches!(field.field_type, FieldType::Integer | FieldType::Number);
+        if !numeric && (field.min.is_some() || field.max.is_some() || field.monotonic) {
+            return invalid(format!("min, max and monotonic only apply to integer and number fields, not {}", field.name));
+        }
+        if field.field_type != FieldType::String && (field.min_length.is_some() || field.max_length.is_some()) {
+            return invalid(format!("min_length and max_length only apply to string fields, not {}", field.name));
+        }
+        if matches!((field.min, field.max), (Some(min), Some(max)) if max < min) {
+            return invalid(format!("max is less than min for {}", field.name));
+        }
+        if matches!((field.min_length, field.max_length), (Some(min), Some(max)) if max < min) {
+            return invalid(format!("max_length is less than min_length for {}", field.name));
+        }
+        if [field.min_length, field.max_length].into_iter().flatten().any(|length| length > GUEST_USIZE_MAX) {
+            return invalid(format!("min_length and max_length can't be more than {} for {}", GUEST_USIZE_MAX, field.name));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(json: &str) -> Result<Schema> {
+        let schema: Schema = serde_json::from_str(json).unwrap();
+        check(&schema).map(|_| schema)
+    }
+
+    fn invalid(json: &str) -> String {
+        parse(json).unwrap_err().message
+    }
+
+    const CSV_SCHEMA: &str = r#"{
+        "format": "csv",
+        "max_size": 4096,
+        "fields": [
+            { "name": "time", "type": "integer", "required": true, "min": 1600000000, "monotonic": true },
+            { "name": "id", "type": "string", "max_length": 64, "unique": true },
+            { "name": "ok", "type": "boolean" }
+        ]
+    }"#;
+
+    #[test]
+    fn guestlib_source_declares_the_schema() {
+        let schema = parse(CSV_SCHEMA).unwrap();
+        let hash = hash(&schema).unwrap();
+        let expected = format!(
+            r#"// Generated by ceres from verifier/schema.json, edit that file instead
+use ceres_guest::schema::{{self, Field, FieldType, Format, Schema}};
+
+pub const SCHEMA_HASH: &str = "{}";
+
+const FIELDS: &[Field] = &[
+    Field {{ name: "time", field_type: FieldType::Integer, required: true, min: Some(1600000000.0), max: None, min_length: None, max_length: None, unique: false, monotonic: true }},
+    Field {{ name: "id", field_type: FieldType::String, required: false, min: None, max: None, min_length: None, max_length: Some(64), unique: true, monotonic: false }},
+    Field {{ name: "ok", field_type: FieldType::Boolean, required: false, min: None, max: None, min_length: None, max_length: None, unique: false, monotonic: false }},
+];
+
+const SCHEMA: Schema = Schema {{ format: Format::Csv, min_size: 0, max_size: 4096, fields: FIELDS }};
+
+pub fn verify(data: &Vec<u8>) -> bool {{
+    if let Err(err) = schema::validate(data, &SCHEMA) {{
+        panic!("the data does not match the schema: {{}}", err);
+    }}
+    true
+}}
+"#,
+            hash
+        );
+        assert_eq!(guestlib_source(&schema).unwrap(), expected);
+    }
+
+    /// Validates `data` with ceres_guest against the schema, with the same values guestlib_source writes out.
+    fn validate(schema: &Schema, data: &[u8]) -> std::result::Result<usize, ceres_guest::Error> {
+        use ceres_guest::schema as guest;
+
+        let fields: Vec<guest::Field> = schema
+            .fields
+            .iter()
+            .map(|field| guest::Field {
+                name: &field.name,
+                field_type: match field.field_type {
+                    FieldType::String => guest::FieldType::String,
+                    FieldType::Integer => guest::FieldType::Integer,
+                    FieldType::Number => guest::FieldType::Number,
+                    FieldType::Boolean => guest::FieldType::Boolean,
+                },
+                required: field.required,
+                min: field.min,
+                max: field.max,
+                min_length: field.min_length,
+                max_length: field.max_length,
+                unique: field.unique,
+                monotonic: field.monotonic,
+            })
+            .collect();
+        let format = match schema.format {
+            Format::Json => guest::Format::Json,
+            Format::JsonArray => guest::Format::JsonArray,
+            Format::Csv => guest::Format::Csv,
+        };
+        let max_size = schema.max_size.unwrap_or(usize::MAX);
+        guest::validate(data, &guest::Schema { format, min_size: schema.min_size, max_size, fields: &fields })
+    }
+
+    fn violation(record: usize, field: usize, reason: &'static str) -> std::result::Result<usize, ceres_guest::Error> {
+        Err(ceres_guest::Error::SchemaViolation { record, field, reason })
+    }
+
+    #[test]
+    fn schema_json_checks_csv_data() {
+        let schema = parse(CSV_SCHEMA).unwrap();
+        assert_eq!(validate(&schema, b"time,id,ok\n1600000000,a,true\n1600000000,b,\n1600000001,,false\n"), Ok(3));
+        assert_eq!(validate(&schema, b"time,id,ok\n,a,true\n"), violation(0, 0, "empty value"));
+        assert_eq!(validate(&schema, b"time,id,ok\n1600000000,a,\n1600000001,a,\n"), violation(1, 1, "duplicate value"));
+        assert_eq!(validate(&schema, b"time,id,ok\n1600000005,a,\n1600000001,b,\n"), violation(1, 0, "value out of order"));
+        assert_eq!(validate(&schema, b"time,id,ok\n1500000000,a,\n"), violation(0, 0, "value out of range"));
+    }
+
+    #[test]
+    fn schema_json_checks_json_array_data() {
+        let schema = parse(&CSV_SCHEMA.replace("\"csv\"", "\"json-array\"")).unwrap();
+        let valid = r#"[{"time": 1600000000, "id": "a", "ok": true}, {"time": 1600000000, "extra": 1}, {"time": 1600000002, "id": "b"}]"#;
+        assert_eq!(validate(&schema, valid.as_bytes()), Ok(3));
+        assert_eq!(validate(&schema, br#"[{"time": 1600000000}, {"id": "a"}]"#), violation(1, 0, "missing field"));
+        assert_eq!(
+            validate(&schema, br#"[{"time": 1600000000, "id": "a"}, {"time": 1600000001, "id": "a"}]"#),
+            violation(1, 1, "duplicate value")
+        );
+        assert_eq!(
+            validate(&schema, br#"[{"time": 1600000001}, {"time": 1600000000}]"#),
+            violation(1, 0, "value out of order")
+        );
+    }
+
+    #[test]
+    fn guestlib_source_has_no_size_limit_by_default() {
+        let schema = parse(r#"{ "format": "json-array", "fields": [{ "name": "id", "type": "string" }] }"#).unwrap();
+        assert!(guestlib_source(&schema)
+            .unwrap()
+            .contains("Schema { format: Format::JsonArray, min_size: 0, max_size: usize::MAX, fields: FIELDS }"));
+    }
+
+    #[test]
+    fn hash_ignores_formatting() {
+        let compact: String = CSV_SCHEMA.split_whitespace().collect::<Vec<_>>().join(" ");
+        assert_eq!(hash(&parse(CSV_SCHEMA).unwrap()).unwrap(), hash(&parse(&compact).unwrap()).unwrap());
+
+        let other = CSV_SCHEMA.replace("\"max_length\": 64", "\"max_length\": 65");
+        assert_ne!(hash(&parse(CSV_SCHEMA).unwrap()).unwrap(), hash(&parse(&other).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn check_rejects_inconsistent_schemas() {
+        assert_eq!(invalid(r#"{ "format": "json", "fields": [] }"#), "Invalid schema.json: the schema has no fields");
+        assert_eq!(
+            invalid(r#"{ "format": "json", "min_size": 10, "max_size": 5, "fields": [{ "name": "a", "type": "string" }] }"#),
+            "Invalid schema.json: max_size is less than min_size"
+        );
+        assert_eq!(
+            invalid(r#"{ "format": "json", "max_size": 4294967296, "fields": [{ "name": "a", "type": "string" }] }"#),
+            "Invalid schema.json: min_size and max_size can't be more than 4294967295 bytes"
+        );
+        assert_eq!(
+            invalid(r#"{ "format": "json", "min_size": 4294967296, "fields": [{ "name": "a", "type": "string" }] }"#),
+            "Invalid schema.json: min_size and max_size can't be more than 4294967295 bytes"
+        );
+        assert_eq!(
+            invalid(r#"{ "format": "json", "fields": [{ "name": "a", "type": "string", "max_length": 4294967296 }] }"#),
+            "Invalid schema.json: min_length and max_length can't be more than 4294967295 for a"
+        );
+        assert!(parse(r#"{ "format": "json", "max_size": 4294967295, "fields": [{ "name": "a", "type": "string" }] }"#).is_ok());
+        assert_eq!(
+            invalid(r#"{ "format": "json", "fields": [{ "name": "", "type": "string" }] }"#),
+            "Invalid schema.json: field 0 has no name"
+        );
+        assert_eq!(
+            invalid(r#"{ "format": "csv", "fields": [{ "name": "a", "type": "string" }, { "name": "a", "type": "integer" }] }"#),
+            "Invalid schema.json: field a is declared twice"
+        );
+        assert_eq!(
+            invalid(r#"{ "format": "json", "fields": [{ "name": "id", "type": "string", "monotonic": true }] }"#),
+            "Invalid schema.json: min, max and monotonic only apply to integer and number fields, not id"
+        );
+        assert_eq!(
+            invalid(r#"{ "format": "json", "fields": [{ "name": "n", "type": "number", "max_length": 3 }] }"#),
+            "Invalid schema.json: min_length and max_length only apply to string fields, not n"
+        );
+        assert_eq!(
+            invalid(r#"{ "format": "json", "fields": [{ "name": "n", "type": "number", "min": 2, "max": 1 }] }"#),
+            "Invalid schema.json: max is less than min for n"
+        );
+        assert_eq!(
+            invalid(r#"{ "format": "json", "fields": [{ "name": "s", "type": "string", "min_length": 2, "max_length": 1 }] }"#),
+            "Invalid schema.json: max_length is less than min_length for s"
+        );
+    }
+
+    #[test]
+    fn unknown_options_are_rejected() {
+        assert!(serde_json::from_str::<Schema>(r#"{ "format": "json", "fields": [{ "name": "a", "type": "string", "uniq": true }] }"#).is_err());
+        assert!(serde_json::from_str::<Schema>(r#"{ "format": "xml", "fields": [{ "name": "a", "type": "string" }] }"#).is_err());
+    }
+}
@@ -0,0 +1,247 @@
+/*
+Collector signing keys. `ceres keygen` creates a key pair for the project:
+- collector.key in the project root: the private key the collector signs its data with. Keep it out of git.
+- verifier/collector.pub: the public key that `ceres gen` embeds in the guest.
+Both files hold one line, `<scheme>:<hex key>`.
+
+Collectors sign their data with `ceres sign`, which writes the signed envelope described in ceres_guest::signature.
+*/
+use ceres_guest::signature;
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+
+use crate::{
+    error::{CeresError, IoContext, Result},
+    lock,
+    project::Project,
+};
+
+pub use ceres_guest::signature::Scheme;
+
+pub const PRIVATE_KEY_FILE: &str = "collector.key";
+pub const PUBLIC_KEY_FILE: &str = "collector.pub";
+
+#[derive(Debug, Clone)]
+pub struct Key {
+    pub scheme: Scheme,
+    pub bytes: Vec<u8>,
+}
+
+impl Key {
+    pub fn to_hex(&self) -> String {
+        lock::to_hex(&self.bytes)
+    }
+}
+
+#[derive(Debug)]
+pub struct KeyFiles {
+    pub private_key: PathBuf,
+    pub public_key: PathBuf,
+    pub public: Key,
+}
+
+/// Creates a new collector key pair for the project. Existing keys are never overwritten.
+pub fn keygen(project: &Project, scheme: Scheme) -> Result<KeyFiles> {
+    let private_key = project.root().join(PRIVATE_KEY_FILE);
+    let public_key = project.verifier_dir().join(PUBLIC_KEY_FILE);
+    if private_key.exists() || public_key.exists() {
+        return Err(CeresError::user_input("The project already has a collector key")
+            .with_hint(format!("Delete {} and verifier/{} to create a new one", PRIVATE_KEY_FILE, PUBLIC_KEY_FILE)));
+    }
+
+    let secret = loop {
+        let mut secret = vec![0u8; 32];
+        getrandom::getrandom(&mut secret)
+            .map_err(|err| CeresError::environment(format!("Failed to generate a random key: {}", err)))?;
+        // almost every 32 byte string is a valid secp256k1 scalar, retry on the rare ones that aren't
+        if public_from_secret(scheme, &secret).is_ok() {
+            break secret;
+        }
+    };
+    let public = Key { scheme, bytes: public_from_secret(scheme, &secret)? };
+
+    write_private_key(&private_key, &Key { scheme, bytes: secret })?;
+    fs::write(&public_key, key_file_content(&public)).context(format!("Failed to write {}", public_key.display()))?;
+    ignore_private_key(project)?;
+
+    Ok(KeyFiles { private_key, public_key, public })
+}
+
+/// The collector public key in verifier/collector.pub, if the project has one.
+pub fn public_key(project: &Project) -> Result<Option<Key>> {
+    let path = project.verifier_dir().join(PUBLIC_KEY_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    read_key(&path).map(Some)
+}
+
+/// Signs the contents of `data_file` with the private key in `key_file` and writes the signed envelope to `output`.
+pub fn sign_file(key_file: &Path, data_file: &Path, output: &Path) -> Result<()> {
+    let key = read_key(key_file)?;
+    let payload = fs::read(data_file).context(format!("Failed to read {}", data_file.display()))?;
+    let envelope = sign(&key, &payload)?;
+    fs::write(output, envelope).context(format!("Failed to write {}", output.display()))
+}
+
+/// Signs `payload` with a private key and returns the signed envelope.
+pub fn sign(private_key: &Key, payload: &[u8]) -> Result<Vec<u8>> {
+    let invalid_key = || CeresError::user_input(format!("Invalid {} private key", private_key.scheme.name()));
+
+    let (public_key, signature) = match private_key.scheme {
+        Scheme::Ed25519 => {
+            use ed25519_dalek::{Signer, SigningKey};
+
+            let secret: [u8; 32] = private_key.bytes.as_slice().try_into().map_err(|_| invalid_key())?;
+            let key = SigningKey::from_bytes(&secret);
+            (key.verifying_key().to_bytes().to_vec(), key.sign(payload).to_bytes().to_vec())
+        }
+        Scheme::Secp256k1 => {
+            use k256::ecdsa::{signature::Signer, Signature, SigningKey};
+
+            let key = SigningKey::from_slice(&private_key.bytes).map_err(|_| invalid_key())?;
+            let signature: Signature = key.sign(payload);
+            (key.verifying_key().to_sec1_bytes().to_vec(), signature.to_bytes().to_vec())
+        }
+    };
+
+    Ok(signature::encode(private_key.scheme, &public_key, &signature, payload))
+}
+
+fn public_from_secret(scheme: Scheme, secret: &[u8]) -> Result<Vec<u8>> {
+    let signed = sign(&Key { scheme, bytes: secret.to_vec() }, &[])?;
+    Ok(signature::open(&signed)
+        .map_err(|err| CeresError::user_input(err.to_string()))?
+        .public_key
+        .to_vec())
+}
+
+fn read_key(path: &Path) -> Result<Key> {
+    let content = fs::read_to_string(path).map_err(|err| {
+        CeresError::user_input(format!("Failed to read {}: {}", path.display(), err))
+            .with_hint("Run \"ceres keygen\" to create a collector key")
+    })?;
+
+    let invalid = || {
+        CeresError::user_input(format!("{} is not a valid key file", path.display()))
+            .with_hint("Key files hold one line: ed25519:<hex key> or secp256k1:<hex key>")
+    };
+    let (scheme, hex) = content.trim().split_once(':').ok_or_else(invalid)?;
+    let scheme = Scheme::from_name(scheme).ok_or_else(invalid)?;
+    let bytes = from_hex(hex).ok_or_else(invalid)?;
+    Ok(Key { scheme, bytes })
+}
+
+fn key_file_content(key: &Key) -> String {
+    format!("{}:{}\n", key.scheme.name(), key.to_hex())
+}
+
+/// Creates the private key file readable by its owner only.
+fn write_private_key(path: &Path, key: &Key) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+
+    let mut file = options.open(path).context(format!("Failed to create {}", path.display()))?;
+    file.write_all(key_file_content(key).as_bytes()).context(format!("Failed to write {}", path.display()))
+}
+
+/// Adds the private key to the project .gitignore so it doesn't end up in the published source code.
+fn ignore_private_key(project: &Project) -> Result<()> {
+    let gitignore_path = project.root().join(".gitignore");
+    let mut gitignore = fs::read_to_string(&gitignore_path).unwrap_or_default();
+    if gitignore.lines().any(|line| line.trim() == PRIVATE_KEY_FILE) {
+        return Ok(());
+    }
+    if !gitignore.is_empty() && !gitignore.ends_with('\n') {
+        gitignore.push('\n');
+    }
+    gitignore.push_str(PRIVATE_KEY_FILE);
+    gitignore.push('\n');
+    fs::write(&gitignore_path, gitignore).context("Failed to update .gitignore")
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ceres_guest::Error;
+
+    const PAYLOAD: &[u8] = b"reading,value\n1,21\n";
+
+    fn round_trip(scheme: Scheme) {
+        let private_key = Key { scheme, bytes: vec![3; 32] };
+        let public_key = public_from_secret(scheme, &private_key.bytes).unwrap();
+        assert_eq!(public_key.len(), scheme.public_key_len());
+
+        let envelope = sign(&private_key, PAYLOAD).unwrap();
+        let signed = signature::verify(&envelope, scheme, &public_key).unwrap();
+        assert_eq!(signed.payload, PAYLOAD);
+
+        let mut tampered = envelope.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert_eq!(signature::verify(&tampered, scheme, &public_key).err(), Some(Error::InvalidSignature));
+
+        let other_key = public_from_secret(scheme, &[4; 32]).unwrap();
+        assert_eq!(signature::verify(&envelope, scheme, &other_key).err(), Some(Error::UntrustedKey));
+
+        let truncated = &envelope[..envelope.len() - PAYLOAD.len() - 1];
+        assert!(matches!(signature::verify(truncated, scheme, &public_key), Err(Error::InvalidEnvelope { .. })));
+    }
+
+    #[test]
+    fn ed25519_signatures_verify_in_the_guest() {
+        round_trip(Scheme::Ed25519);
+    }
+
+    #[test]
+    fn secp256k1_signatures_verify_in_the_guest() {
+        round_trip(Scheme::Secp256k1);
+    }
+
+    #[test]
+    fn rejects_invalid_private_keys() {
+        assert!(sign(&Key { scheme: Scheme::Ed25519, bytes: vec![3; 31] }, PAYLOAD).is_err());
+        assert!(sign(&Key { scheme: Scheme::Secp256k1, bytes: vec![0; 32] }, PAYLOAD).is_err());
+    }
+
+    #[test]
+    fn keygen_writes_the_public_key_of_the_private_key() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("verifier")).unwrap();
+        let project = Project::open(dir.path()).unwrap();
+
+        for scheme in [Scheme::Ed25519, Scheme::Secp256k1] {
+            let files = keygen(&project, scheme).unwrap();
+            let private_key = read_key(&files.private_key).unwrap();
+            let public_key = public_key(&project).unwrap().unwrap();
+            assert_eq!(public_key.scheme, scheme);
+            assert_eq!(public_key.bytes, files.public.bytes);
+            assert_eq!(public_from_secret(scheme, &private_key.bytes).unwrap(), public_key.bytes);
+            assert!(keygen(&project, scheme).is_err());
+
+            let envelope = sign(&private_key, PAYLOAD).unwrap();
+            assert!(signature::verify(&envelope, scheme, &public_key.bytes).is_ok());
+
+            fs::remove_file(files.private_key).unwrap();
+            fs::remove_file(files.public_key).unwrap();
+        }
+        let gitignore = fs::read_to_string(dir.path().join(".gitignore")).unwrap();
+        assert_eq!(gitignore, "collector.key\n");
+    }
+}
@@ -0,0 +1,185 @@
+/*
+Where a project stands, for `ceres status`. Every step is missing, up to date, or stale when something it depends on changed:
+- generated: verifier/out/<name> is stale when the verifier source changed since it was generated
+- build: ceres.lock is stale when the verifier source changed since the build
+- pr: PR.md is stale when its image ID is not the one of an up to date build
+- receipts: a receipt is stale when it is older than the last build
+*/
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    dev, gen, lock,
+    pr::{self, PR_FILE},
+    project::Project,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    Missing,
+    Stale,
+    UpToDate,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            State::Missing => "missing",
+            State::Stale => "stale",
+            State::UpToDate => "up to date",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Step {
+    pub state: State,
+    /// What was found, or what to run next.
+    pub detail: String,
+}
+
+#[derive(Debug)]
+pub struct Receipt {
+    pub path: PathBuf,
+    /// Older than the last build.
+    pub stale: bool,
+}
+
+#[derive(Debug)]
+pub struct ProjectStatus {
+    pub generated: Step,
+    pub build: Step,
+    /// The image ID recorded by the last build, even if it is stale.
+    pub image_id: Option<String>,
+    pub pr: Step,
+    pub samples: Vec<PathBuf>,
+    /// Receipt files in the project root, the verifier folder and the generated project.
+    pub receipts: Vec<Receipt>,
+}
+
+pub fn status(project: &Project) -> ProjectStatus {
+    let build_lock = lock::read(project.root());
+    let build = build_step(project, build_lock.as_ref());
+    let pr = pr_step(project, build_lock.as_ref(), &build);
+
+    ProjectStatus {
+        generated: generated_step(project),
+        image_id: build_lock.map(|build_lock| build_lock.image_id),
+        build,
+        pr,
+        samples: dev::sample_files(project),
+        receipts: receipts(project),
+    }
+}
+
+fn step(state: State, detail: impl Into<String>) -> Step {
+    Step { state, detail: detail.into() }
+}
+
+fn generated_step(project: &Project) -> Step {
+    let out_dir = project.out_dir();
+    if !out_dir.exists() {
+        return step(State::Missing, "Run \"ceres gen\"");
+    }
+
+    if gen::is_stale(project) {
+        step(State::Stale, "the verifier source changed since it was generated. Run \"ceres dev\" to re-sync")
+    } else {
+        step(State::UpToDate, out_dir.strip_prefix(project.root()).unwrap_or(&out_dir).display().to_string())
+    }
+}
+
+fn build_step(project: &Project, build_lock: Option<&lock::Lock>) -> Step {
+    let build_lock = match build_lock {
+        Some(build_lock) => build_lock,
+        None => return step(State::Missing, "Run \"ceres build\""),
+    };
+
+    if build_lock.source_hash != lock::source_hash(&project.verifier_dir()) {
+        return step(
+            State::Stale,
+            format!("image ID {}, but the verifier source changed since the build. Run \"ceres build\"", build_lock.image_id),
+        );
+    }
+    let host_binary = build_lock.host_binary_path(project.root());
+    if !host_binary.exists() {
+        return step(
+            State::Stale,
+            format!("image ID {}, but the verifier executable is missing. Run \"ceres build\"", build_lock.image_id),
+        );
+    }
+    step(State::UpToDate, format!("image ID {}", build_lock.image_id))
+}
+
+fn pr_step(project: &Project, build_lock: Option<&lock::Lock>, build: &Step) -> Step {
+    let pr_file = project.root().join(PR_FILE);
+    if !pr_file.exists() {
+        return step(State::Missing, "Run \"ceres new-pr\"");
+    }
+    let pr = match pr::read(&pr_file) {
+        Ok(pr) => pr,
+        Err(err) => return step(State::Stale, format!("{}. Run \"ceres new-pr\"", err.message)),
+    };
+
+    match build_lock {
+        Some(build_lock) if build_lock.image_id != pr.image_id => step(
+            State::Stale,
+            format!("PR image ID {} is not the one of the last build. Run \"ceres new-pr\"", pr.image_id),
+        ),
+        Some(_) if build.state != State::UpToDate => {
+            step(State::Stale, "prepared from a stale build. Run \"ceres build\" and \"ceres new-pr\"")
+        }
+        _ => step(State::UpToDate, format!("PR.md for {}", pr.name)),
+    }
+}
+
+fn receipts(project: &Project) -> Vec<Receipt> {
+    let built_at = modified(&project.root().join(lock::LOCK_FILE));
+    let mut receipts = Vec::new();
+    for dir in [project.root().to_path_buf(), project.verifier_dir(), project.out_dir()] {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_file() && path.extension().is_some_and(|extension| extension == "bin") {
+                let stale = matches!((modified(&path), built_at), (Some(receipt), Some(build)) if receipt < build);
+                receipts.push(Receipt { path, stale });
+            }
+        }
+    }
+    receipts.sort_by(|a, b| a.path.cmp(&b.path));
+    receipts
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).ok()?.modified().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_step_compares_the_fingerprint() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("verifier")).unwrap();
+        fs::write(dir.path().join("verifier").join("Cargo.toml"), "[package]\nname = \"verifier\"\n").unwrap();
+        let project = Project::open(dir.path()).unwrap();
+        assert_eq!(generated_step(&project).state, State::Missing);
+
+        fs::create_dir_all(project.out_dir()).unwrap();
+        assert_eq!(generated_step(&project).state, State::Stale);
+
+        let fingerprint = lock::source_hash(&project.verifier_dir());
+        fs::write(project.out_dir().join(gen::FINGERPRINT_FILE), fingerprint).unwrap();
+        assert_eq!(generated_step(&project).state, State::UpToDate);
+
+        fs::write(dir.path().join("verifier").join("Cargo.toml"), "[package]\nname = \"changed\"\n").unwrap();
+        assert_eq!(generated_step(&project).state, State::Stale);
+    }
+}